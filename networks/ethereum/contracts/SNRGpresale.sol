@@ -96,6 +96,18 @@ contract SNRGPresale is Ownable2Step, ReentrancyGuard, Pausable {
     /// @notice Maximum purchase amount per transaction
     uint256 public maxPurchaseAmount;
 
+    /// @notice Typed purchase message domain, shared with the Solana presale
+    string public constant MESSAGE_NAME = "SNRG Presale";
+    /// @notice Typed purchase message version
+    string public constant MESSAGE_VERSION = "2";
+    bytes32 private constant DOMAIN_TYPEHASH =
+        keccak256("EIP712Domain(string name,string version,bytes32 chainTag,bytes32 verifyingContract)");
+    /// @dev Addresses are encoded as left-padded bytes32 so Solana pubkeys fit the same layout
+    bytes32 private constant PURCHASE_TYPEHASH =
+        keccak256(
+            "Purchase(bytes32 buyer,bytes32 recipient,bytes32 referrer,bytes32 paymentToken,uint256 paymentAmount,uint256 snrgAmount,uint256 nonce,uint256 deadline)"
+        );
+
     /* -------------------------------------------------------------------------- */
     /*                                    ERRORS                                  */
     /* -------------------------------------------------------------------------- */
@@ -370,7 +382,8 @@ contract SNRGPresale is Ownable2Step, ReentrancyGuard, Pausable {
 
     /**
      * @notice Build message hash for signature verification
-     * @dev Includes user-scoped nonce and signature deadline, EIP-191 style hash
+     * @dev EIP-712 style typed digest; the layout is shared with the Solana presale
+     *      (see the `snrg_purchase_message` crate) so one signer serves both chains
      */
     function _buildMessageHash(
         address buyer,
@@ -381,18 +394,29 @@ contract SNRGPresale is Ownable2Step, ReentrancyGuard, Pausable {
         uint256 deadline,
         address thisAddr
     ) internal view returns (bytes32) {
-        return keccak256(
+        bytes32 domainSeparator = keccak256(
+            abi.encode(
+                DOMAIN_TYPEHASH,
+                keccak256(bytes(MESSAGE_NAME)),
+                keccak256(bytes(MESSAGE_VERSION)),
+                bytes32(block.chainid),
+                bytes32(uint256(uint160(thisAddr)))
+            )
+        );
+        bytes32 structHash = keccak256(
             abi.encode(
-                buyer,
-                paymentToken,
+                PURCHASE_TYPEHASH,
+                bytes32(uint256(uint160(buyer))),
+                bytes32(uint256(uint160(buyer))), // recipient: EVM purchases always deliver to the buyer
+                bytes32(0), // referrer: the EVM presales pay no referral bonuses
+                bytes32(uint256(uint160(paymentToken))),
                 paymentAmount,
                 snrgAmount,
                 nonce,
-                deadline,
-                block.chainid,
-                thisAddr
+                deadline
             )
         );
+        return MessageHashUtils.toTypedDataHash(domainSeparator, structHash);
     }
 
     /**
@@ -412,8 +436,7 @@ contract SNRGPresale is Ownable2Step, ReentrancyGuard, Pausable {
         if (nonce == 0 || nonce > type(uint128).max) revert InvalidNonce();
         if (_usedNonces[buyer][nonce]) revert NonceAlreadyUsed();
 
-        bytes32 ethSignedMessageHash = MessageHashUtils.toEthSignedMessageHash(messageHash);
-        address recoveredSigner = ECDSA.recover(ethSignedMessageHash, signature);
+        address recoveredSigner = ECDSA.recover(messageHash, signature);
 
         if (recoveredSigner == address(0) || recoveredSigner != signer) revert InvalidSignature();
 
@@ -0,0 +1,252 @@
+//! Typed purchase messages shared by the EVM and Solana SNRG presales.
+//!
+//! Both presales verify the same EIP-712 style digest, so one signing service
+//! can authorize purchases on every chain:
+//!
+//! ```text
+//! digest          = keccak256(0x19 0x01 ‖ domainSeparator ‖ structHash)
+//! domainSeparator = keccak256(DOMAIN_TYPEHASH ‖ keccak256(name) ‖ keccak256(version)
+//!                             ‖ chainTag ‖ verifyingContract)
+//! structHash      = keccak256(PURCHASE_TYPEHASH ‖ buyer ‖ recipient ‖ referrer
+//!                             ‖ paymentToken ‖ paymentAmount ‖ snrgAmount ‖ nonce ‖ deadline)
+//! ```
+//!
+//! Every field is one 32-byte word, exactly as Solidity's `abi.encode` lays it out:
+//!
+//! - `chainTag` — EVM: `block.chainid` as a big-endian `uint256`;
+//!   Solana: `keccak256("solana")`.
+//! - `verifyingContract`, `buyer`, `recipient`, `referrer`, `paymentToken` — EVM addresses are
+//!   left-padded with zeros to 32 bytes; Solana pubkeys are used as-is. Native
+//!   payments (ETH/MATIC/BNB/SOL) use the all-zero word. `buyer` pays, `recipient`
+//!   receives the SNRG; they are equal for ordinary purchases (the EVM presales
+//!   always are). `referrer` is the wallet credited with the referral bonus, or
+//!   the all-zero word when the purchase carries no referral.
+//! - `paymentAmount`, `snrgAmount`, `nonce`, `deadline` — big-endian `uint256`.
+//!
+//! Signatures are plain secp256k1 signatures over `digest` (no
+//! `"\x19Ethereum Signed Message"` prefix). A signer is identified by its EVM
+//! address in the same left-padded 32-byte form, see [`signer_id`].
+
+use sha3::{Digest, Keccak256};
+
+pub const DOMAIN_NAME: &str = "SNRG Presale";
+pub const DOMAIN_VERSION: &str = "2";
+pub const DOMAIN_TYPE: &str =
+    "EIP712Domain(string name,string version,bytes32 chainTag,bytes32 verifyingContract)";
+pub const PURCHASE_TYPE: &str = "Purchase(bytes32 buyer,bytes32 recipient,bytes32 referrer,bytes32 paymentToken,uint256 paymentAmount,uint256 snrgAmount,uint256 nonce,uint256 deadline)";
+pub const SOLANA_CHAIN_TAG_SEED: &[u8] = b"solana";
+
+/// Identifies the chain a purchase is valid on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChainTag {
+    Evm(u64),
+    Solana,
+}
+
+impl ChainTag {
+    pub fn to_word(self) -> [u8; 32] {
+        match self {
+            ChainTag::Evm(chain_id) => uint_word(chain_id as u128),
+            ChainTag::Solana => keccak256(SOLANA_CHAIN_TAG_SEED),
+        }
+    }
+}
+
+/// Domain binding a purchase to one presale deployment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Domain {
+    pub chain_tag: ChainTag,
+    pub verifying_contract: [u8; 32],
+}
+
+impl Domain {
+    pub fn evm(chain_id: u64, presale: [u8; 20]) -> Self {
+        Self { chain_tag: ChainTag::Evm(chain_id), verifying_contract: address_word(presale) }
+    }
+
+    pub fn solana(program_id: [u8; 32]) -> Self {
+        Self { chain_tag: ChainTag::Solana, verifying_contract: program_id }
+    }
+
+    pub fn separator(&self) -> [u8; 32] {
+        keccak256_words(&[
+            keccak256(DOMAIN_TYPE.as_bytes()),
+            keccak256(DOMAIN_NAME.as_bytes()),
+            keccak256(DOMAIN_VERSION.as_bytes()),
+            self.chain_tag.to_word(),
+            self.verifying_contract,
+        ])
+    }
+}
+
+/// A signed purchase authorization.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Purchase {
+    pub buyer: [u8; 32],
+    pub recipient: [u8; 32],
+    pub referrer: [u8; 32],
+    pub payment_token: [u8; 32],
+    pub payment_amount: u128,
+    pub snrg_amount: u128,
+    pub nonce: u128,
+    pub deadline: u64,
+}
+
+impl Purchase {
+    pub fn struct_hash(&self) -> [u8; 32] {
+        keccak256_words(&[
+            keccak256(PURCHASE_TYPE.as_bytes()),
+            self.buyer,
+            self.recipient,
+            self.referrer,
+            self.payment_token,
+            uint_word(self.payment_amount),
+            uint_word(self.snrg_amount),
+            uint_word(self.nonce),
+            uint_word(self.deadline as u128),
+        ])
+    }
+}
+
+/// Digest the presale signers sign and the presale programs recover against.
+pub fn purchase_digest(domain: &Domain, purchase: &Purchase) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update([0x19, 0x01]);
+    hasher.update(domain.separator());
+    hasher.update(purchase.struct_hash());
+    hasher.finalize().into()
+}
+
+/// Left-pads a 20-byte EVM address to a 32-byte word.
+pub fn address_word(address: [u8; 20]) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[12..].copy_from_slice(&address);
+    word
+}
+
+/// Signer identity for an uncompressed secp256k1 public key (64 bytes, no 0x04 prefix):
+/// the signer's EVM address as a left-padded 32-byte word.
+pub fn signer_id(public_key: &[u8; 64]) -> [u8; 32] {
+    let hash = keccak256(public_key);
+    let mut word = [0u8; 32];
+    word[12..].copy_from_slice(&hash[12..]);
+    word
+}
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+fn keccak256_words(words: &[[u8; 32]]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    for word in words {
+        hasher.update(word);
+    }
+    hasher.finalize().into()
+}
+
+fn uint_word(value: u128) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[16..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Golden vectors are `SNRGpresale._buildMessageHash` outputs, computed with an independent
+    // `abi.encode` implementation
+
+    const EVM_PRESALE: [u8; 20] = [0x11; 20];
+    const EVM_BUYER: [u8; 20] = [0x22; 20];
+    const USDT: [u8; 20] = [
+        0xda, 0xc1, 0x7f, 0x95, 0x8d, 0x2e, 0xe5, 0x23, 0xa2, 0x20, 0x62, 0x06, 0x99, 0x45, 0x97, 0xc1, 0x3d,
+        0x83, 0x1e, 0xc7,
+    ];
+
+    fn hex(bytes: [u8; 32]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn evm_domain_separator() {
+        assert_eq!(
+            hex(Domain::evm(1, EVM_PRESALE).separator()),
+            "a3eee0256c89a745c95ad8443fb36fbf5e054ec41d8989f63055e422bc9bae2b"
+        );
+    }
+
+    #[test]
+    fn solana_domain_separator() {
+        assert_eq!(
+            hex(Domain::solana([0x05; 32]).separator()),
+            "9707d11486aa0118e6eb8e8f167dc5aaf952caf0ef3f6d5fab36a1cbae100a01"
+        );
+    }
+
+    #[test]
+    fn native_purchase_digest() {
+        let purchase = Purchase {
+            buyer: address_word(EVM_BUYER),
+            recipient: address_word(EVM_BUYER),
+            referrer: [0; 32],
+            payment_token: [0; 32],
+            payment_amount: 1_000_000_000_000_000_000,
+            snrg_amount: 1_000_000_000_000,
+            nonce: 1,
+            deadline: 1_700_000_000,
+        };
+        assert_eq!(
+            hex(purchase_digest(&Domain::evm(1, EVM_PRESALE), &purchase)),
+            "de96731493d8efd14cf19a969d74a90474a94fe9307073f3ce7f613c8094a6a5"
+        );
+    }
+
+    #[test]
+    fn token_purchase_digest() {
+        let purchase = Purchase {
+            buyer: address_word(EVM_BUYER),
+            recipient: address_word(EVM_BUYER),
+            referrer: [0; 32],
+            payment_token: address_word(USDT),
+            payment_amount: 250_000_000,
+            snrg_amount: 5_000_000_000_000,
+            nonce: 42,
+            deadline: 1_700_000_000,
+        };
+        assert_eq!(
+            hex(purchase_digest(&Domain::evm(1, EVM_PRESALE), &purchase)),
+            "b4f09f38165d085da2be845b120968b063512318fad237d2b12895654fe9ecd6"
+        );
+    }
+
+    #[test]
+    fn recipient_and_referrer_purchase_digest() {
+        let purchase = Purchase {
+            buyer: [0x01; 32],
+            recipient: [0x02; 32],
+            referrer: [0x03; 32],
+            payment_token: [0; 32],
+            payment_amount: 2_000_000_000,
+            snrg_amount: 3_000_000_000_000,
+            nonce: u128::MAX,
+            deadline: 1_800_000_000,
+        };
+        let domain = Domain::solana([0x05; 32]);
+        assert_eq!(
+            hex(purchase_digest(&domain, &purchase)),
+            "4f69c3db33de8d9527c48265a6289db74a3360f7a3ef466d4c0445e176c5dcff"
+        );
+        // The referrer is bound: swapping it invalidates the signature
+        let self_referred = Purchase { referrer: purchase.buyer, ..purchase };
+        assert_ne!(purchase_digest(&domain, &self_referred), purchase_digest(&domain, &purchase));
+    }
+
+    #[test]
+    fn signer_id_is_left_padded_address() {
+        let id = signer_id(&[0xab; 64]);
+        assert_eq!(&id[..12], &[0; 12]);
+        assert_eq!(&id[12..], &keccak256(&[0xab; 64])[12..]);
+    }
+}
@@ -1,6 +1,8 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Mint, Token, TokenAccount, TransferChecked};
 use anchor_lang::solana_program::keccak;
+use snrg_purchase_message::{purchase_digest, signer_id, Domain, Purchase};
+use snrg_staking::program::SnrgStaking;
 use std::collections::BTreeMap;
 
 declare_id!("YourSNRGPresaleProgramIDHere111111111111111111");
@@ -8,6 +10,17 @@ declare_id!("YourSNRGPresaleProgramIDHere111111111111111111");
 pub const PURCHASE_COOLDOWN: i64 = 5 * 60; // 5 minutes
 pub const MAX_PURCHASES_PER_DAY: u64 = 10;
 pub const MIN_PURCHASE_AMOUNT: u64 = 1000 * 1_000_000_000; // 1000 SNRG (9 decimals)
+pub const MAX_REFERRAL_BONUS_BPS: u16 = 1_000; // 10%
+pub const BPS_DENOMINATOR: u64 = 10_000;
+pub const MAX_STATS_PAYMENT_MINTS: usize = 16; // native SOL + supported tokens
+pub const MAX_SIGNERS: usize = 10;
+pub const SIGNER_ROTATION_DELAY: i64 = 48 * 60 * 60; // 48 hours
+pub const MIN_REVEAL_SLOT_GAP: u64 = 2;
+pub const COMMIT_EXPIRY_SLOTS: u64 = 1_500; // ~10 minutes
+pub const SNRG_UNIT: u64 = 1_000_000_000; // auction prices are lamports per whole SNRG
+pub const MAX_REFUND_WINDOW: i64 = 30 * 24 * 60 * 60; // 30 days
+pub const MAX_MILESTONES: usize = 12;
+pub const MAX_STAKE_BONUS_BPS: u16 = 2_000; // 20%
 
 #[program]
 pub mod snrg_presale {
@@ -15,37 +28,109 @@ pub mod snrg_presale {
 
     pub fn initialize(
         ctx: Context<Initialize>,
-        signer: Pubkey,
+        signers: Vec<Pubkey>,
+        threshold: u8,
     ) -> Result<()> {
-        require_keys_neq!(signer, Pubkey::default(), PresaleError::ZeroAddress);
+        _validate_signer_set(&signers, threshold)?;
 
         let presale = &mut ctx.accounts.presale;
+        presale.owner = ctx.accounts.payer.key();
+        presale.pending_owner = Pubkey::default();
         presale.snrg_mint = ctx.accounts.snrg_mint.key();
         presale.treasury = ctx.accounts.treasury.key();
-        presale.signer = signer;
+        presale.signers = signers.clone();
+        presale.threshold = threshold;
+        presale.pending_signers = Vec::new();
+        presale.pending_threshold = 0;
+        presale.signer_set_proposed_at = 0;
         presale.open = false;
         presale.paused = false;
         presale.max_purchase_amount = 5_000_000 * 1_000_000_000; // 5M SNRG default
+        presale.referral_bonus_bps = 0;
+        presale.buyer_referral_bonus_bps = 0;
+        presale.sale_mode = SaleMode::FixedPrice;
+        presale.auction = DutchAuction::default();
+        presale.refund_window = 0;
+        presale.start_time = 0;
+        presale.end_time = 0;
+        presale.proceeds_escrow = false;
+        presale.stake_bonus_bps = 0;
         presale.bump = *ctx.bumps.get("presale").unwrap();
 
+        let stats = &mut ctx.accounts.stats;
+        stats.presale = presale.key();
+        stats.total_snrg_sold = 0;
+        stats.total_raised = BTreeMap::new();
+        stats.unique_buyers = 0;
+        stats.purchases = 0;
+        stats.first_purchase_at = 0;
+        stats.last_purchase_at = 0;
+        stats.bump = ctx.bumps.stats;
+
         emit!(PresaleInitialized {
             snrg_mint: presale.snrg_mint,
             treasury: presale.treasury,
-            signer,
+            signers,
+            threshold,
         });
 
         Ok(())
     }
 
-    pub fn set_signer(ctx: Context<Admin>, new_signer: Pubkey) -> Result<()> {
-        require_keys_neq!(new_signer, Pubkey::default(), PresaleError::ZeroAddress);
+    /// Owner proposes a new signer set – starts the rotation delay
+    pub fn propose_signer_set(ctx: Context<Admin>, signers: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        _validate_signer_set(&signers, threshold)?;
+
         let presale = &mut ctx.accounts.presale;
-        if presale.signer == new_signer {
-            return Ok(()); // avoid re-store
-        }
-        let old = presale.signer;
-        presale.signer = new_signer;
-        emit!(SignerSet { old_signer: old, new_signer });
+        require!(presale.signer_set_proposed_at == 0, PresaleError::SignerSetPending);
+
+        let now = Clock::get()?.unix_timestamp;
+        presale.pending_signers = signers.clone();
+        presale.pending_threshold = threshold;
+        presale.signer_set_proposed_at = now;
+
+        emit!(SignerSetProposed {
+            signers,
+            threshold,
+            eta: now + SIGNER_ROTATION_DELAY,
+        });
+        Ok(())
+    }
+
+    /// Owner confirms after the rotation delay → new signer set becomes active
+    pub fn confirm_signer_set(ctx: Context<Admin>) -> Result<()> {
+        let presale = &mut ctx.accounts.presale;
+        require!(presale.signer_set_proposed_at != 0, PresaleError::NoPendingSignerSet);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            now >= presale.signer_set_proposed_at + SIGNER_ROTATION_DELAY,
+            PresaleError::RotationDelayNotElapsed
+        );
+
+        let old_signers = std::mem::take(&mut presale.signers);
+        presale.signers = std::mem::take(&mut presale.pending_signers);
+        presale.threshold = presale.pending_threshold;
+        presale.pending_threshold = 0;
+        presale.signer_set_proposed_at = 0;
+
+        emit!(SignerSetConfirmed {
+            old_signers,
+            new_signers: presale.signers.clone(),
+            threshold: presale.threshold,
+        });
+        Ok(())
+    }
+
+    pub fn cancel_signer_set(ctx: Context<Admin>) -> Result<()> {
+        let presale = &mut ctx.accounts.presale;
+        require!(presale.signer_set_proposed_at != 0, PresaleError::NoPendingSignerSet);
+
+        let signers = std::mem::take(&mut presale.pending_signers);
+        presale.pending_threshold = 0;
+        presale.signer_set_proposed_at = 0;
+
+        emit!(SignerSetCanceled { signers });
         Ok(())
     }
 
@@ -59,6 +144,24 @@ pub mod snrg_presale {
         Ok(())
     }
 
+    /// Owner schedules the sale window (end_time 0 = open-ended) and arms the sale;
+    /// `set_open(false)` still closes it manually at any time
+    pub fn set_schedule(ctx: Context<Admin>, start_time: i64, end_time: i64) -> Result<()> {
+        require_gt!(start_time, 0, PresaleError::InvalidSchedule);
+        require!(end_time == 0 || end_time > start_time, PresaleError::InvalidSchedule);
+
+        let presale = &mut ctx.accounts.presale;
+        presale.start_time = start_time;
+        presale.end_time = end_time;
+        emit!(ScheduleSet { start_time, end_time });
+
+        if !presale.open {
+            presale.open = true;
+            emit!(OpenSet { open: true });
+        }
+        Ok(())
+    }
+
     pub fn set_max_purchase_amount(ctx: Context<Admin>, amount: u64) -> Result<()> {
         require_gt!(amount, 0, PresaleError::ZeroAmount);
         ctx.accounts.presale.max_purchase_amount = amount;
@@ -84,56 +187,172 @@ pub mod snrg_presale {
         Ok(())
     }
 
+    pub fn set_referral_bonus(ctx: Context<Admin>, referrer_bps: u16, buyer_bps: u16) -> Result<()> {
+        require!(referrer_bps <= MAX_REFERRAL_BONUS_BPS, PresaleError::BonusTooHigh);
+        require!(buyer_bps <= MAX_REFERRAL_BONUS_BPS, PresaleError::BonusTooHigh);
+
+        let presale = &mut ctx.accounts.presale;
+        presale.referral_bonus_bps = referrer_bps;
+        presale.buyer_referral_bonus_bps = buyer_bps;
+        emit!(ReferralBonusSet { referrer_bps, buyer_bps });
+        Ok(())
+    }
+
+    /// Owner sets the annual reward bonus for purchases staked through `stake_duration_days`
+    pub fn set_stake_bonus(ctx: Context<Admin>, bonus_bps: u16) -> Result<()> {
+        require!(bonus_bps <= MAX_STAKE_BONUS_BPS, PresaleError::BonusTooHigh);
+        ctx.accounts.presale.stake_bonus_bps = bonus_bps;
+        emit!(StakeBonusSet { bonus_bps });
+        Ok(())
+    }
+
+    /// Owner sets the buyer withdrawal window (0 disables); existing receipts keep their deadline
+    pub fn set_refund_window(ctx: Context<Admin>, refund_window: i64) -> Result<()> {
+        require!((0..=MAX_REFUND_WINDOW).contains(&refund_window), PresaleError::InvalidRefundWindow);
+        let presale = &mut ctx.accounts.presale;
+        if presale.refund_window == refund_window {
+            return Ok(());
+        }
+        presale.refund_window = refund_window;
+        emit!(RefundWindowSet { refund_window });
+        Ok(())
+    }
+
+    /// Referrer opens their referral PDA so purchases can credit it
+    pub fn register_referrer(ctx: Context<RegisterReferrer>) -> Result<()> {
+        let referral = &mut ctx.accounts.referral;
+        referral.presale = ctx.accounts.presale.key();
+        referral.referrer = ctx.accounts.referrer.key();
+        referral.referred_volume = 0;
+        referral.referred_purchases = 0;
+        referral.bonus_earned = 0;
+        referral.bump = ctx.bumps.referral;
+
+        emit!(ReferrerRegistered { referrer: referral.referrer });
+        Ok(())
+    }
+
     pub fn buy_with_native(
         ctx: Context<BuyWithNative>,
         payment_amount: u64,
         snrg_amount: u64,
         nonce: u128,
         deadline: i64,
-        signature: [u8; 65],
+        signatures: Vec<[u8; 65]>,
+        stake_duration_days: Option<u64>,
     ) -> Result<()> {
         let presale = &ctx.accounts.presale;
-        require!(presale.open, PresaleError::PresaleClosed);
-        require!(!presale.paused, PresaleError::Paused);
+        _require_live(presale)?;
+        require!(presale.sale_mode == SaleMode::FixedPrice, PresaleError::WrongSaleMode);
         require_gt!(payment_amount, 0, PresaleError::ZeroAmount);
         require_gt!(snrg_amount, 0, PresaleError::ZeroAmount);
         require_gt!(deadline, 0, PresaleError::SignatureExpired);
         require!(Clock::get()?.unix_timestamp <= deadline, PresaleError::SignatureExpired);
 
         let buyer = ctx.accounts.buyer.key();
+        let recipient = ctx.accounts.recipient.key();
         let payment_token = Pubkey::default(); // native SOL
+        let refundable = presale.refund_window > 0;
+        require!(ctx.accounts.receipt.is_some() == refundable, PresaleError::InvalidReceipt);
+
+        if stake_duration_days.is_some() {
+            // The staking program takes the position owner's signature, and staked SNRG can't be clawed back
+            require_keys_eq!(recipient, buyer, PresaleError::StakeRequiresBuyer);
+            require!(!refundable, PresaleError::StakeNotRefundable);
+        }
 
-        _check_purchase_limits(presale, buyer, snrg_amount)?;
-        let message = _build_message_hash(buyer, payment_token, payment_amount, snrg_amount, nonce, deadline)?;
-        _verify_signature(&presale.signer, message, &signature, buyer, nonce, &mut ctx.accounts.nonce_state)?;
+        _check_purchase_limits(presale, &ctx.accounts.tracking, snrg_amount)?;
+        let referrer = ctx.accounts.referral.as_ref().map(|r| r.referrer).unwrap_or_default();
+        let message =
+            _build_message_hash(buyer, recipient, referrer, payment_token, payment_amount, snrg_amount, nonce, deadline)?;
+        _verify_signatures(presale, message, &signatures, buyer, nonce, &mut ctx.accounts.nonce_state)?;
 
-        // Transfer SOL to treasury
+        // Transfer SOL to treasury (or the proceeds vault), or escrow it in the receipt
+        // while the refund window is open
+        let destination = match ctx.accounts.receipt.as_ref() {
+            Some(receipt) => receipt.to_account_info(),
+            None => _native_proceeds_destination(
+                presale,
+                ctx.accounts.treasury.to_account_info(),
+                &mut ctx.accounts.proceeds_vault,
+                payment_amount,
+            )?,
+        };
         let ix = anchor_lang::solana_program::system_instruction::transfer(
             &buyer,
-            &presale.treasury,
+            destination.key,
             payment_amount,
         );
         anchor_lang::solana_program::program::invoke(
             &ix,
             &[
                 ctx.accounts.buyer.to_account_info(),
-                ctx.accounts.treasury.to_account_info(),
+                destination,
             ],
         )?;
 
         _deliver_snrg_exact(
             &ctx.accounts.treasury_snrgtoken,
-            &ctx.accounts.buyer_snrgtoken,
+            &ctx.accounts.recipient_snrgtoken,
             &ctx.accounts.treasury_signer,
             presale,
             snrg_amount,
             &ctx.accounts.token_program,
         )?;
 
+        let first_purchase = ctx.accounts.tracking.last_purchase_time == 0;
         _update_purchase_tracking(&mut ctx.accounts.tracking)?;
+        _record_sale(&mut ctx.accounts.stats, payment_token, payment_amount, snrg_amount, first_purchase)?;
+
+        let (buyer_bonus, deferred_bonus) = _credit_referral(
+            &mut ctx.accounts.referral,
+            &mut ctx.accounts.referrer_snrgtoken,
+            &mut ctx.accounts.recipient_snrgtoken,
+            &ctx.accounts.treasury_snrgtoken,
+            &ctx.accounts.treasury_signer,
+            presale,
+            buyer,
+            recipient,
+            snrg_amount,
+            refundable,
+            &ctx.accounts.token_program,
+        )?;
+
+        if let Some(receipt) = ctx.accounts.receipt.as_mut() {
+            // Any referral bonus paid to the buyer must be handed back on refund too
+            let returnable = snrg_amount.checked_add(buyer_bonus).ok_or(PresaleError::MathOverflow)?;
+            _open_receipt(receipt, presale, buyer, recipient, payment_token, payment_amount, returnable, nonce)?;
+            // The referrer is paid when the receipt is swept, so a refund leaves nothing to claw back
+            receipt.referrer = referrer;
+            receipt.referrer_bonus = deferred_bonus;
+            receipt.referred_amount = snrg_amount;
+        }
+
+        if let Some(duration_days) = stake_duration_days {
+            _stake_purchase(
+                &ctx.accounts.staking_program,
+                &ctx.accounts.staking,
+                &ctx.accounts.user_stakes,
+                &ctx.accounts.stake_position,
+                &ctx.accounts.staking_vault,
+                &ctx.accounts.staking_vault_authority,
+                &ctx.accounts.snrg_mint,
+                &ctx.accounts.buyer,
+                &mut ctx.accounts.recipient_snrgtoken,
+                &ctx.accounts.treasury_snrgtoken,
+                &ctx.accounts.treasury_signer,
+                presale,
+                snrg_amount,
+                buyer_bonus,
+                duration_days,
+                &ctx.accounts.token_program,
+                &ctx.accounts.system_program,
+            )?;
+        }
 
         emit!(Purchased {
             buyer,
+            recipient,
             payment_token,
             snrg_amount,
             paid_amount: payment_amount,
@@ -148,11 +367,12 @@ pub mod snrg_presale {
         snrg_amount: u64,
         nonce: u128,
         deadline: i64,
-        signature: [u8; 65],
+        signatures: Vec<[u8; 65]>,
+        stake_duration_days: Option<u64>,
     ) -> Result<()> {
         let presale = &ctx.accounts.presale;
-        require!(presale.open, PresaleError::PresaleClosed);
-        require!(!presale.paused, PresaleError::Paused);
+        _require_live(presale)?;
+        require!(presale.sale_mode == SaleMode::FixedPrice, PresaleError::WrongSaleMode);
         require_gt!(payment_amount, 0, PresaleError::ZeroAmount);
         require_gt!(snrg_amount, 0, PresaleError::ZeroAmount);
         require_gt!(deadline, 0, PresaleError::SignatureExpired);
@@ -162,19 +382,43 @@ pub mod snrg_presale {
         require!(presale.supported_tokens.contains_key(&payment_mint), PresaleError::TokenNotSupported);
 
         let buyer = ctx.accounts.buyer.key();
+        let recipient = ctx.accounts.recipient.key();
+        let refundable = presale.refund_window > 0;
+        require!(ctx.accounts.receipt.is_some() == refundable, PresaleError::InvalidReceipt);
 
-        _check_purchase_limits(presale, buyer, snrg_amount)?;
-        let message = _build_message_hash(buyer, payment_mint, payment_amount, snrg_amount, nonce, deadline)?;
-        _verify_signature(&presale.signer, message, &signature, buyer, nonce, &mut ctx.accounts.nonce_state)?;
+        if stake_duration_days.is_some() {
+            // The staking program takes the position owner's signature, and staked SNRG can't be clawed back
+            require_keys_eq!(recipient, buyer, PresaleError::StakeRequiresBuyer);
+            require!(!refundable, PresaleError::StakeNotRefundable);
+        }
+
+        _check_purchase_limits(presale, &ctx.accounts.tracking, snrg_amount)?;
+        let referrer = ctx.accounts.referral.as_ref().map(|r| r.referrer).unwrap_or_default();
+        let message =
+            _build_message_hash(buyer, recipient, referrer, payment_mint, payment_amount, snrg_amount, nonce, deadline)?;
+        _verify_signatures(presale, message, &signatures, buyer, nonce, &mut ctx.accounts.nonce_state)?;
+
+        // Payment goes to the treasury or the proceeds vault, or to the presale escrow while
+        // the refund window is open
+        let destination = match ctx.accounts.escrow_payment_token.as_mut() {
+            Some(escrow) if refundable => escrow,
+            None if !refundable && presale.proceeds_escrow => {
+                let vault = ctx.accounts.proceeds_vault.as_mut().ok_or(PresaleError::InvalidProceedsVault)?;
+                vault.deposited = vault.deposited.checked_add(payment_amount).ok_or(PresaleError::MathOverflow)?;
+                ctx.accounts.proceeds_vault_token.as_mut().ok_or(PresaleError::InvalidProceedsVault)?
+            }
+            None if !refundable => &mut ctx.accounts.treasury_payment_token,
+            _ => return err!(PresaleError::InvalidReceipt),
+        };
 
         // Transfer payment token with exact-delivery check
-        let before = ctx.accounts.treasury_payment_token.amount;
+        let before = destination.amount;
         token::transfer_checked(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
                 TransferChecked {
                     from: ctx.accounts.buyer_payment_token.to_account_info(),
-                    to: ctx.accounts.treasury_payment_token.to_account_info(),
+                    to: destination.to_account_info(),
                     authority: ctx.accounts.buyer.to_account_info(),
                     mint: ctx.accounts.payment_mint.to_account_info(),
                 },
@@ -183,22 +427,71 @@ pub mod snrg_presale {
             ctx.accounts.payment_mint.decimals,
         )?;
 
-        let after = ctx.accounts.treasury_payment_token.reload()?.amount;
+        let after = destination.reload()?.amount;
         require!(after >= before + payment_amount, PresaleError::UnderpaidTreasury);
 
         _deliver_snrg_exact(
             &ctx.accounts.treasury_snrgtoken,
-            &ctx.accounts.buyer_snrgtoken,
+            &ctx.accounts.recipient_snrgtoken,
             &ctx.accounts.treasury_signer,
             presale,
             snrg_amount,
             &ctx.accounts.token_program,
         )?;
 
+        let first_purchase = ctx.accounts.tracking.last_purchase_time == 0;
         _update_purchase_tracking(&mut ctx.accounts.tracking)?;
+        _record_sale(&mut ctx.accounts.stats, payment_mint, payment_amount, snrg_amount, first_purchase)?;
+
+        let (buyer_bonus, deferred_bonus) = _credit_referral(
+            &mut ctx.accounts.referral,
+            &mut ctx.accounts.referrer_snrgtoken,
+            &mut ctx.accounts.recipient_snrgtoken,
+            &ctx.accounts.treasury_snrgtoken,
+            &ctx.accounts.treasury_signer,
+            presale,
+            buyer,
+            recipient,
+            snrg_amount,
+            refundable,
+            &ctx.accounts.token_program,
+        )?;
+
+        if let Some(receipt) = ctx.accounts.receipt.as_mut() {
+            // Any referral bonus paid to the buyer must be handed back on refund too
+            let returnable = snrg_amount.checked_add(buyer_bonus).ok_or(PresaleError::MathOverflow)?;
+            _open_receipt(receipt, presale, buyer, recipient, payment_mint, payment_amount, returnable, nonce)?;
+            // The referrer is paid when the receipt is swept, so a refund leaves nothing to claw back
+            receipt.referrer = referrer;
+            receipt.referrer_bonus = deferred_bonus;
+            receipt.referred_amount = snrg_amount;
+        }
+
+        if let Some(duration_days) = stake_duration_days {
+            _stake_purchase(
+                &ctx.accounts.staking_program,
+                &ctx.accounts.staking,
+                &ctx.accounts.user_stakes,
+                &ctx.accounts.stake_position,
+                &ctx.accounts.staking_vault,
+                &ctx.accounts.staking_vault_authority,
+                &ctx.accounts.snrg_mint,
+                &ctx.accounts.buyer,
+                &mut ctx.accounts.recipient_snrgtoken,
+                &ctx.accounts.treasury_snrgtoken,
+                &ctx.accounts.treasury_signer,
+                presale,
+                snrg_amount,
+                buyer_bonus,
+                duration_days,
+                &ctx.accounts.token_program,
+                &ctx.accounts.system_program,
+            )?;
+        }
 
         emit!(Purchased {
             buyer,
+            recipient,
             payment_token: payment_mint,
             snrg_amount,
             paid_amount: payment_amount,
@@ -207,226 +500,2003 @@ pub mod snrg_presale {
         Ok(())
     }
 
-    pub fn pause(ctx: Context<Admin>) -> Result<()> {
-        let presale = &mut ctx.accounts.presale;
-        require!(!presale.paused, PresaleError::AlreadyPaused);
-        presale.paused = true;
-        emit!(ContractPaused { caller: ctx.accounts.owner.key() });
+    /// Phase 1 of a native purchase: escrow the payment and a hash of the purchase parameters
+    pub fn commit_purchase(ctx: Context<CommitPurchase>, commitment: [u8; 32], deposit: u64) -> Result<()> {
+        let presale = &ctx.accounts.presale;
+        _require_live(presale)?;
+        require!(presale.sale_mode == SaleMode::FixedPrice, PresaleError::WrongSaleMode);
+        require_gt!(deposit, 0, PresaleError::ZeroAmount);
+
+        let ix = anchor_lang::solana_program::system_instruction::transfer(
+            &ctx.accounts.buyer.key(),
+            &ctx.accounts.purchase_commitment.key(),
+            deposit,
+        );
+        anchor_lang::solana_program::program::invoke(
+            &ix,
+            &[
+                ctx.accounts.buyer.to_account_info(),
+                ctx.accounts.purchase_commitment.to_account_info(),
+            ],
+        )?;
+
+        let clock = Clock::get()?;
+        let pc = &mut ctx.accounts.purchase_commitment;
+        pc.buyer = ctx.accounts.buyer.key();
+        pc.commitment = commitment;
+        pc.deposit = deposit;
+        pc.committed_slot = clock.slot;
+        pc.committed_at = clock.unix_timestamp;
+        pc.bump = ctx.bumps.purchase_commitment;
+
+        emit!(PurchaseCommitted {
+            buyer: pc.buyer,
+            commitment,
+            deposit,
+            slot: pc.committed_slot,
+        });
         Ok(())
     }
 
-    pub fn unpause(ctx: Context<Admin>) -> Result<()> {
-        let presale = &mut ctx.accounts.presale;
-        require!(presale.paused, PresaleError::NotPaused);
-        presale.paused = false;
-        emit!(ContractUnpaused { caller: ctx.accounts.owner.key() });
+    /// Phase 2: reveal the committed purchase; the signed quote is honored as of commit time
+    pub fn reveal_purchase(
+        ctx: Context<RevealPurchase>,
+        snrg_amount: u64,
+        nonce: u128,
+        deadline: i64,
+        salt: [u8; 32],
+        signatures: Vec<[u8; 65]>,
+    ) -> Result<()> {
+        let presale = &ctx.accounts.presale;
+        _require_live(presale)?;
+        require!(presale.sale_mode == SaleMode::FixedPrice, PresaleError::WrongSaleMode);
+        require_gt!(snrg_amount, 0, PresaleError::ZeroAmount);
+
+        let buyer = ctx.accounts.buyer.key();
+        let recipient = ctx.accounts.recipient.key();
+        let payment_token = Pubkey::default(); // native SOL
+        let pc = &ctx.accounts.purchase_commitment;
+        let payment_amount = pc.deposit;
+
+        let slot = Clock::get()?.slot;
+        require!(slot >= pc.committed_slot + MIN_REVEAL_SLOT_GAP, PresaleError::RevealTooEarly);
+        require!(slot <= pc.committed_slot + COMMIT_EXPIRY_SLOTS, PresaleError::CommitmentExpired);
+        require!(
+            _commitment_hash(buyer, recipient, payment_amount, snrg_amount, nonce, deadline, &salt) == pc.commitment,
+            PresaleError::CommitmentMismatch
+        );
+        require!(deadline > 0 && pc.committed_at <= deadline, PresaleError::SignatureExpired);
+
+        _check_purchase_limits(presale, &ctx.accounts.tracking, snrg_amount)?;
+        let referrer = ctx.accounts.referral.as_ref().map(|r| r.referrer).unwrap_or_default();
+        let message =
+            _build_message_hash(buyer, recipient, referrer, payment_token, payment_amount, snrg_amount, nonce, deadline)?;
+        _verify_signatures(presale, message, &signatures, buyer, nonce, &mut ctx.accounts.nonce_state)?;
+
+        // Release escrowed payment to treasury or the proceeds vault (or the receipt while the
+        // refund window is open); rent goes back to the buyer on close
+        let refundable = presale.refund_window > 0;
+        require!(ctx.accounts.receipt.is_some() == refundable, PresaleError::InvalidReceipt);
+        let destination = match ctx.accounts.receipt.as_ref() {
+            Some(receipt) => receipt.to_account_info(),
+            None => _native_proceeds_destination(
+                presale,
+                ctx.accounts.treasury.to_account_info(),
+                &mut ctx.accounts.proceeds_vault,
+                payment_amount,
+            )?,
+        };
+        **ctx.accounts.purchase_commitment.to_account_info().try_borrow_mut_lamports()? -= payment_amount;
+        **destination.try_borrow_mut_lamports()? += payment_amount;
+
+        _deliver_snrg_exact(
+            &ctx.accounts.treasury_snrgtoken,
+            &ctx.accounts.recipient_snrgtoken,
+            &ctx.accounts.treasury_signer,
+            presale,
+            snrg_amount,
+            &ctx.accounts.token_program,
+        )?;
+
+        let first_purchase = ctx.accounts.tracking.last_purchase_time == 0;
+        _update_purchase_tracking(&mut ctx.accounts.tracking)?;
+        _record_sale(&mut ctx.accounts.stats, payment_token, payment_amount, snrg_amount, first_purchase)?;
+
+        let (buyer_bonus, deferred_bonus) = _credit_referral(
+            &mut ctx.accounts.referral,
+            &mut ctx.accounts.referrer_snrgtoken,
+            &mut ctx.accounts.recipient_snrgtoken,
+            &ctx.accounts.treasury_snrgtoken,
+            &ctx.accounts.treasury_signer,
+            presale,
+            buyer,
+            recipient,
+            snrg_amount,
+            refundable,
+            &ctx.accounts.token_program,
+        )?;
+
+        if let Some(receipt) = ctx.accounts.receipt.as_mut() {
+            // Any referral bonus paid to the buyer must be handed back on refund too
+            let returnable = snrg_amount.checked_add(buyer_bonus).ok_or(PresaleError::MathOverflow)?;
+            _open_receipt(receipt, presale, buyer, recipient, payment_token, payment_amount, returnable, nonce)?;
+            // The referrer is paid when the receipt is swept, so a refund leaves nothing to claw back
+            receipt.referrer = referrer;
+            receipt.referrer_bonus = deferred_bonus;
+            receipt.referred_amount = snrg_amount;
+        }
+
+        emit!(Purchased {
+            buyer,
+            recipient,
+            payment_token,
+            snrg_amount,
+            paid_amount: payment_amount,
+        });
+
         Ok(())
     }
 
-    // View functions
-    pub fn get_remaining_purchases_today(ctx: Context<ViewTracking>) -> Result<u64> {
-        let now = Clock::get()?.unix_timestamp;
-        let t = &ctx.accounts.tracking;
-        if now >= t.daily_purchase, Ok(MAX_PURCHASES_PER_DAY)
-        } else if t.purchase_count_today >= MAX_PURCHASES_PER_DAY {
-            Ok(0)
-        } else {
-            Ok(MAX_PURCHASES_PER_DAY - t.purchase_count_today)
-        }
+    /// Buyer reclaims the deposit of a commitment that was never revealed in time
+    pub fn refund_commitment(ctx: Context<RefundCommitment>) -> Result<()> {
+        let pc = &ctx.accounts.purchase_commitment;
+        let slot = Clock::get()?.slot;
+        require!(slot > pc.committed_slot + COMMIT_EXPIRY_SLOTS, PresaleError::CommitmentNotExpired);
+
+        // Deposit and rent are returned by `close = buyer`
+        emit!(CommitmentRefunded { buyer: pc.buyer, deposit: pc.deposit });
+        Ok(())
     }
 
-    pub fn get_time_till_next_purchase(ctx: Context<ViewTracking>) -> Result<i64> {
+    /// Owner configures a descending-price auction and switches the sale to auction mode
+    pub fn configure_auction(
+        ctx: Context<Admin>,
+        start_price: u64,
+        floor_price: u64,
+        start_time: i64,
+        duration: i64,
+        curve: DecayCurve,
+        supply: u64,
+    ) -> Result<()> {
+        require_gt!(floor_price, 0, PresaleError::ZeroAmount);
+        require_gte!(start_price, floor_price, PresaleError::InvalidAuctionConfig);
+        require_gt!(duration, 0, PresaleError::InvalidAuctionConfig);
+        require_gte!(supply, MIN_PURCHASE_AMOUNT, PresaleError::AmountTooLow);
+
+        // A new auction may start only once the previous one has ended and every bid is claimed
+        let presale = &mut ctx.accounts.presale;
         let now = Clock::get()?.unix_timestamp;
-        let end = ctx.accounts.tracking.last_purchase_time + PURCHASE_COOLDOWN;
-        Ok(end.saturating_sub(now).max(0))
+        require_eq!(presale.auction.open_bids, 0, PresaleError::AuctionInProgress);
+        require!(
+            presale.auction.duration == 0 || _auction_ended(&presale.auction, now),
+            PresaleError::AuctionInProgress
+        );
+
+        presale.sale_mode = SaleMode::DutchAuction;
+        presale.auction = DutchAuction {
+            start_price,
+            floor_price,
+            start_time,
+            duration,
+            curve,
+            supply,
+            total_bid: 0,
+            clearing_price: 0,
+            ended_at: 0,
+            open_bids: 0,
+        };
+
+        emit!(AuctionConfigured {
+            start_price,
+            floor_price,
+            start_time,
+            duration,
+            curve,
+            supply,
+        });
+        Ok(())
     }
 
-    pub fn is_nonce_used(ctx: Context<ViewNonce>, nonce: u128) -> Result<bool> {
-        Ok(ctx.accounts.nonce_state.used_nonces.contains_key(&nonce))
+    /// Owner switches back to signed fixed-price purchases once an auction is over
+    pub fn set_sale_mode(ctx: Context<Admin>, sale_mode: SaleMode) -> Result<()> {
+        let presale = &mut ctx.accounts.presale;
+        if presale.sale_mode == sale_mode {
+            return Ok(());
+        }
+        if presale.sale_mode == SaleMode::DutchAuction {
+            let now = Clock::get()?.unix_timestamp;
+            require!(_auction_ended(&presale.auction, now), PresaleError::AuctionInProgress);
+        }
+        presale.sale_mode = sale_mode;
+        emit!(SaleModeSet { sale_mode });
+        Ok(())
     }
-}
 
-// Internal helpers
-fn _check_purchase_limits(presale: &Presale, buyer: Pubkey, snrg_amount: u64) -> Result<()> {
-    require_gte!(snrg_amount, MIN_PURCHASE_AMOUNT, PresaleError::AmountTooLow);
-    require!(snrg_amount <= presale.max_purchase_amount, PresaleError::AmountTooHigh);
-    Ok(())
-}
+    /// Bid at the current auction price; payment is escrowed until settlement at the clearing price.
+    /// The signer set authorizes each bid up to `max_payment` lamports
+    pub fn place_bid(
+        ctx: Context<PlaceBid>,
+        snrg_amount: u64,
+        max_payment: u64,
+        nonce: u128,
+        deadline: i64,
+        signatures: Vec<[u8; 65]>,
+    ) -> Result<()> {
+        let presale = &ctx.accounts.presale;
+        _require_live(presale)?;
+        require!(presale.sale_mode == SaleMode::DutchAuction, PresaleError::WrongSaleMode);
+        require_gt!(snrg_amount, 0, PresaleError::ZeroAmount);
+        require_gt!(deadline, 0, PresaleError::SignatureExpired);
+        require!(Clock::get()?.unix_timestamp <= deadline, PresaleError::SignatureExpired);
 
-fn _build_message_hash(
-    buyer: Pubkey,
-    payment_token: Pubkey,
-    payment_amount: u64,
-    snrg_amount: u64,
-    nonce: u128,
-    deadline: i64,
-) -> Result<[u8; 32]> {
-    let mut data = Vec::new();
-    data.extend_from_slice(buyer.as_ref());
-    data.extend_from_slice(payment_token.as_ref());
-    data.extend_from_slice(&payment_amount.to_le_bytes());
-    data.extend_from_slice(&snrg_amount.to_le_bytes());
-    data.extend_from_slice(&nonce.to_le_bytes());
-    data.extend_from_slice(&deadline.to_le_bytes());
-    data.extend_from_slice(&crate::ID.as_ref()); // chain_id equivalent
-    data.extend_from_slice(b"snrg_presale_v1"); // domain separator
+        // Limits apply to the bidder's whole position, not each top-up
+        let bidder = ctx.accounts.bidder.key();
+        let first_bid = ctx.accounts.bid.snrg_amount == 0;
+        let cumulative = ctx.accounts.bid.snrg_amount.checked_add(snrg_amount).ok_or(PresaleError::MathOverflow)?;
+        _check_purchase_amount(presale, cumulative)?;
 
-    Ok(keccak::hash(&data).0)
-}
+        let message = _build_message_hash(
+            bidder,
+            bidder,
+            Pubkey::default(),
+            Pubkey::default(), // native SOL
+            max_payment,
+            snrg_amount,
+            nonce,
+            deadline,
+        )?;
+        _verify_signatures(presale, message, &signatures, bidder, nonce, &mut ctx.accounts.nonce_state)?;
 
-fn _verify_signature(
-    signer: &Pubkey,
-    message_hash: [u8; 32],
-    signature: &[u8; 65],
-    buyer: Pubkey,
-    nonce: u128,
-    nonce_state: &mut Account<NonceState>,
-) -> Result<()> {
-    require!(nonce > 0 && nonce <= u128::from(u128::MAX), PresaleError::InvalidNonce);
+        let now = Clock::get()?.unix_timestamp;
+        let auction = &presale.auction;
+        require!(now >= auction.start_time, PresaleError::AuctionNotStarted);
+        require!(!_auction_ended(auction, now), PresaleError::AuctionEnded);
 
-    if nonce_state.used_nonces.contains_key(&nonce) {
-        return err!(PresaleError::NonceAlreadyUsed);
-    }
+        let remaining = auction.supply - auction.total_bid;
+        require!(snrg_amount <= remaining, PresaleError::AmountTooHigh);
 
-    let eth_signed_hash = {
-        let mut prefixed = b"\x19Ethereum Signed Message:\n32".to_vec();
-        prefixed.extend_from_slice(&message_hash);
-        keccak::hash(&prefixed).0
-    };
+        let price = _auction_price(auction, now)?;
+        let cost = _auction_cost(snrg_amount, price)?;
+        require!(cost <= max_payment, PresaleError::BidExceedsAuthorized);
 
-    let pubkey = solana_program::pubkey::Pubkey::try_from(
-        solana_program::secp256k1_recover::secp256k1_recover(&eth_signed_hash, signature[64], &signature[..64])
-            .map_err(|_| PresaleError::InvalidSignature)?
-            .as_ref(),
-    )
-    .map_err(|_| PresaleError::InvalidSignature)?;
+        let ix = anchor_lang::solana_program::system_instruction::transfer(
+            &bidder,
+            &ctx.accounts.bid.key(),
+            cost,
+        );
+        anchor_lang::solana_program::program::invoke(
+            &ix,
+            &[
+                ctx.accounts.bidder.to_account_info(),
+                ctx.accounts.bid.to_account_info(),
+            ],
+        )?;
 
-    require_keys_eq!(pubkey, *signer, PresaleError::InvalidSignature);
+        let bid = &mut ctx.accounts.bid;
+        bid.bidder = bidder;
+        bid.snrg_amount = bid.snrg_amount.checked_add(snrg_amount).ok_or(PresaleError::MathOverflow)?;
+        bid.paid = bid.paid.checked_add(cost).ok_or(PresaleError::MathOverflow)?;
+        bid.bump = ctx.bumps.bid;
 
-    nonce_state.used_nonces.insert(nonce, true);
-    emit!(SignatureVerified { buyer, nonce });
+        let auction = &mut ctx.accounts.presale.auction;
+        auction.total_bid += snrg_amount;
+        if first_bid {
+            auction.open_bids = auction.open_bids.checked_add(1).ok_or(PresaleError::MathOverflow)?;
+        }
+        if auction.total_bid == auction.supply {
+            // Sold out: every bidder settles at the price of the final bid
+            auction.clearing_price = price;
+            auction.ended_at = now;
+        }
 
-    Ok(())
-}
+        emit!(BidPlaced { bidder, snrg_amount, price, paid: cost });
+        Ok(())
+    }
 
-fn _deliver_snrg_exact(
-    treasury_token: &Account<TokenAccount>,
-    buyer_token: &mut Account<TokenAccount>,
-    treasury_signer: &UncheckedAccount,
-    presale: &Presale,
-    amount: u64,
-    token_program: &Program<Token>,
-) -> Result<()> {
-    let before_buyer = buyer_token.amount;
-    let before_treasury = treasury_token.amount;
-    require!(before_treasury >= amount, PresaleError::InsufficientBalance);
+    /// After the auction ends, bidder receives SNRG and any overpayment above the clearing price
+    pub fn claim_auction(ctx: Context<ClaimAuction>) -> Result<()> {
+        let presale = &ctx.accounts.presale;
+        require!(!presale.paused, PresaleError::Paused);
+        let now = Clock::get()?.unix_timestamp;
+        require!(_auction_ended(&presale.auction, now), PresaleError::AuctionInProgress);
 
-    let seeds = &[b"presale", presale.treasury.as_ref(), &[presale.bump]];
-    let signer = &[&seeds[..]];
+        let clearing_price = if presale.auction.clearing_price > 0 {
+            presale.auction.clearing_price
+        } else {
+            presale.auction.floor_price
+        };
 
-    token::transfer_checked(
-        CpiContext::new_with_signer(
-            token_program.to_account_info(),
-            TransferChecked {
-                from: treasury_token.to_account_info(),
+        let bid = &ctx.accounts.bid;
+        let bidder = bid.bidder;
+        let snrg_amount = bid.snrg_amount;
+        let owed = _auction_cost(snrg_amount, clearing_price)?.min(bid.paid);
+        let refund = bid.paid - owed;
+
+        // Proceeds to treasury or the proceeds vault; the refund and rent return to the bidder on close
+        let destination = _native_proceeds_destination(
+            presale,
+            ctx.accounts.treasury.to_account_info(),
+            &mut ctx.accounts.proceeds_vault,
+            owed,
+        )?;
+        **ctx.accounts.bid.to_account_info().try_borrow_mut_lamports()? -= owed;
+        **destination.try_borrow_mut_lamports()? += owed;
+
+        _deliver_snrg_exact(
+            &ctx.accounts.treasury_snrgtoken,
+            &ctx.accounts.bidder_snrgtoken,
+            &ctx.accounts.treasury_signer,
+            presale,
+            snrg_amount,
+            &ctx.accounts.token_program,
+        )?;
+
+        let first_purchase = ctx.accounts.tracking.last_purchase_time == 0;
+        _update_purchase_tracking(&mut ctx.accounts.tracking)?;
+        _record_sale(&mut ctx.accounts.stats, Pubkey::default(), owed, snrg_amount, first_purchase)?;
+
+        let auction = &mut ctx.accounts.presale.auction;
+        auction.open_bids = auction.open_bids.saturating_sub(1);
+
+        emit!(AuctionSettled {
+            bidder,
+            snrg_amount,
+            clearing_price,
+            paid: owed,
+            refund,
+        });
+        Ok(())
+    }
+
+    /// Recipient exercises the withdrawal right: SNRG goes back to the treasury, payment back to the payer
+    pub fn refund_purchase(ctx: Context<RefundPurchase>) -> Result<()> {
+        let receipt = &ctx.accounts.receipt;
+        let now = Clock::get()?.unix_timestamp;
+        require!(now <= receipt.refundable_until, PresaleError::RefundWindowClosed);
+
+        // Return SNRG with exact-delivery check
+        let before = ctx.accounts.treasury_snrgtoken.amount;
+        token::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.recipient_snrgtoken.to_account_info(),
+                    to: ctx.accounts.treasury_snrgtoken.to_account_info(),
+                    authority: ctx.accounts.recipient.to_account_info(),
+                    mint: ctx.accounts.snrg_mint.to_account_info(),
+                },
+            ),
+            receipt.snrg_amount,
+            ctx.accounts.snrg_mint.decimals,
+        )?;
+        let after = ctx.accounts.treasury_snrgtoken.reload()?.amount;
+        require_eq!(after - before, receipt.snrg_amount, PresaleError::InexactDelivery);
+
+        // Native payments sit in the receipt itself and are returned by `close = buyer`
+        if receipt.payment_token != Pubkey::default() {
+            _release_escrowed_tokens(
+                &ctx.accounts.escrow_payment_token,
+                &ctx.accounts.buyer_payment_token,
+                &ctx.accounts.payment_mint,
+                &ctx.accounts.presale,
+                receipt,
+                &ctx.accounts.token_program,
+            )?;
+        }
+
+        let stats = &mut ctx.accounts.stats;
+        stats.total_snrg_sold = stats.total_snrg_sold.saturating_sub(receipt.snrg_amount);
+        if let Some(raised) = stats.total_raised.get_mut(&receipt.payment_token) {
+            *raised = raised.saturating_sub(receipt.paid_amount);
+        }
+
+        emit!(PurchaseRefunded {
+            buyer: receipt.buyer,
+            payment_token: receipt.payment_token,
+            snrg_amount: receipt.snrg_amount,
+            refunded_amount: receipt.paid_amount,
+        });
+        Ok(())
+    }
+
+    /// Anyone: once a receipt's refund window has passed, release its escrowed payment to the
+    /// treasury (or the proceeds vault while milestones gate proceeds) and pay any held referrer bonus
+    pub fn sweep_receipt(ctx: Context<SweepReceipt>) -> Result<()> {
+        let presale = &ctx.accounts.presale;
+        let receipt = &ctx.accounts.receipt;
+        let now = Clock::get()?.unix_timestamp;
+        require!(now > receipt.refundable_until, PresaleError::RefundWindowOpen);
+
+        if receipt.payment_token == Pubkey::default() {
+            // Rent goes back to the buyer on close
+            let destination = _native_proceeds_destination(
+                presale,
+                ctx.accounts.treasury.to_account_info(),
+                &mut ctx.accounts.proceeds_vault,
+                receipt.paid_amount,
+            )?;
+            **ctx.accounts.receipt.to_account_info().try_borrow_mut_lamports()? -= receipt.paid_amount;
+            **destination.try_borrow_mut_lamports()? += receipt.paid_amount;
+        } else {
+            let destination = if presale.proceeds_escrow {
+                let vault = ctx.accounts.proceeds_vault.as_mut().ok_or(PresaleError::InvalidProceedsVault)?;
+                vault.deposited = vault.deposited.checked_add(receipt.paid_amount).ok_or(PresaleError::MathOverflow)?;
+                &ctx.accounts.proceeds_vault_token
+            } else {
+                &ctx.accounts.treasury_payment_token
+            };
+            _release_escrowed_tokens(
+                &ctx.accounts.escrow_payment_token,
+                destination,
+                &ctx.accounts.payment_mint,
+                &ctx.accounts.presale,
+                receipt,
+                &ctx.accounts.token_program,
+            )?;
+        }
+
+        if receipt.referrer_bonus > 0 {
+            let (referral, referrer_token, treasury_token, treasury_signer) = match (
+                ctx.accounts.referral.as_mut(),
+                ctx.accounts.referrer_snrgtoken.as_mut(),
+                &ctx.accounts.treasury_snrgtoken,
+                &ctx.accounts.treasury_signer,
+            ) {
+                (Some(r), Some(rt), Some(t), Some(ts)) => (r, rt, t, ts),
+                _ => return err!(PresaleError::InvalidReferrer),
+            };
+            require_keys_eq!(referrer_token.owner, receipt.referrer, PresaleError::InvalidReferrer);
+            _pay_referrer(
+                referral,
+                referrer_token,
+                treasury_token,
+                treasury_signer,
+                presale,
+                receipt.recipient,
+                receipt.referred_amount,
+                receipt.referrer_bonus,
+                0,
+                &ctx.accounts.token_program,
+            )?;
+        }
+
+        emit!(ProceedsReleased {
+            buyer: receipt.buyer,
+            payment_token: receipt.payment_token,
+            amount: receipt.paid_amount,
+        });
+        Ok(())
+    }
+
+    /// Owner sets the proceeds release schedule once; from then on payments settle into proceeds vaults.
+    /// A percentage schedule unlocks a share of every vault's deposits, so it fits all payment mints.
+    /// An amount schedule is denominated in one payment mint; other mints unlock at the final milestone
+    pub fn configure_milestones(
+        ctx: Context<ConfigureMilestones>,
+        kind: MilestoneKind,
+        milestones: Vec<Milestone>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.presale.proceeds_escrow, PresaleError::MilestonesLocked);
+        require!(!milestones.is_empty() && milestones.len() <= MAX_MILESTONES, PresaleError::InvalidMilestones);
+
+        let mut total: u64 = 0;
+        for (i, milestone) in milestones.iter().enumerate() {
+            require_gt!(milestone.value, 0, PresaleError::InvalidMilestones);
+            if i > 0 {
+                require!(milestone.unlock_time > milestones[i - 1].unlock_time, PresaleError::InvalidMilestones);
+            }
+            total = total.checked_add(milestone.value).ok_or(PresaleError::MathOverflow)?;
+        }
+        match kind {
+            MilestoneKind::Percentage => require_eq!(total, BPS_DENOMINATOR, PresaleError::InvalidMilestones),
+            MilestoneKind::Amount(mint) => require!(
+                mint == Pubkey::default() || ctx.accounts.presale.supported_tokens.contains_key(&mint),
+                PresaleError::TokenNotSupported
+            ),
+        }
+
+        let schedule = &mut ctx.accounts.milestone_schedule;
+        schedule.presale = ctx.accounts.presale.key();
+        schedule.kind = kind;
+        schedule.milestones = milestones.clone();
+        schedule.bump = ctx.bumps.milestone_schedule;
+
+        ctx.accounts.presale.proceeds_escrow = true;
+
+        emit!(MilestonesConfigured { kind, milestones });
+        Ok(())
+    }
+
+    /// Opens the refund escrow for a payment mint; refundable token payments wait here
+    pub fn open_payment_escrow(ctx: Context<OpenPaymentEscrow>) -> Result<()> {
+        let payment_token = ctx.accounts.payment_mint.key();
+        require!(ctx.accounts.presale.supported_tokens.contains_key(&payment_token), PresaleError::TokenNotSupported);
+        emit!(PaymentEscrowOpened { payment_token, escrow: ctx.accounts.escrow_payment_token.key() });
+        Ok(())
+    }
+
+    /// Opens the proceeds vault for a payment mint (default pubkey = native SOL)
+    pub fn open_proceeds_vault(ctx: Context<OpenProceedsVault>, payment_token: Pubkey) -> Result<()> {
+        let presale = &ctx.accounts.presale;
+        require!(
+            payment_token == Pubkey::default() || presale.supported_tokens.contains_key(&payment_token),
+            PresaleError::TokenNotSupported
+        );
+
+        let vault = &mut ctx.accounts.proceeds_vault;
+        vault.presale = presale.key();
+        vault.payment_token = payment_token;
+        vault.deposited = 0;
+        vault.released = 0;
+        vault.bump = ctx.bumps.proceeds_vault;
+
+        emit!(ProceedsVaultOpened { payment_token });
+        Ok(())
+    }
+
+    /// Treasury withdraws whatever the milestones reached so far have unlocked
+    pub fn release_proceeds(ctx: Context<ReleaseProceeds>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let vault = &ctx.accounts.proceeds_vault;
+        let (unlocked, milestones_reached) = _unlocked_proceeds(&ctx.accounts.milestone_schedule, vault, now)?;
+        require_gt!(milestones_reached, 0, PresaleError::MilestoneNotReached);
+
+        let amount = unlocked.saturating_sub(vault.released);
+        require_gt!(amount, 0, PresaleError::NothingToRelease);
+
+        let payment_token = vault.payment_token;
+        if payment_token == Pubkey::default() {
+            **ctx.accounts.proceeds_vault.to_account_info().try_borrow_mut_lamports()? -= amount;
+            **ctx.accounts.treasury.to_account_info().try_borrow_mut_lamports()? += amount;
+        } else {
+            let (vault_token, treasury_token, payment_mint) = match (
+                &ctx.accounts.proceeds_vault_token,
+                &ctx.accounts.treasury_payment_token,
+                &ctx.accounts.payment_mint,
+            ) {
+                (Some(v), Some(t), Some(m)) => (v, t, m),
+                _ => return err!(PresaleError::InvalidProceedsVault),
+            };
+            require_keys_eq!(payment_mint.key(), payment_token, PresaleError::InvalidProceedsVault);
+
+            let presale_key = ctx.accounts.presale.key();
+            let seeds = &[b"proceeds", presale_key.as_ref(), payment_token.as_ref(), &[vault.bump]];
+            token::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: vault_token.to_account_info(),
+                        to: treasury_token.to_account_info(),
+                        authority: ctx.accounts.proceeds_vault.to_account_info(),
+                        mint: payment_mint.to_account_info(),
+                    },
+                    &[&seeds[..]],
+                ),
+                amount,
+                payment_mint.decimals,
+            )?;
+        }
+
+        let vault = &mut ctx.accounts.proceeds_vault;
+        vault.released = vault.released.checked_add(amount).ok_or(PresaleError::MathOverflow)?;
+
+        emit!(MilestoneProceedsReleased {
+            payment_token,
+            amount,
+            milestones_reached,
+            total_released: vault.released,
+        });
+        Ok(())
+    }
+
+    pub fn pause(ctx: Context<Admin>) -> Result<()> {
+        let presale = &mut ctx.accounts.presale;
+        require!(!presale.paused, PresaleError::AlreadyPaused);
+        presale.paused = true;
+        emit!(ContractPaused { caller: ctx.accounts.owner.key() });
+        Ok(())
+    }
+
+    pub fn unpause(ctx: Context<Admin>) -> Result<()> {
+        let presale = &mut ctx.accounts.presale;
+        require!(presale.paused, PresaleError::NotPaused);
+        presale.paused = false;
+        emit!(ContractUnpaused { caller: ctx.accounts.owner.key() });
+        Ok(())
+    }
+
+    /// Owner nominates a successor, who takes over once they call `accept_ownership`
+    /// (`Pubkey::default()` cancels a pending nomination)
+    pub fn transfer_ownership(ctx: Context<Admin>, new_owner: Pubkey) -> Result<()> {
+        let presale = &mut ctx.accounts.presale;
+        presale.pending_owner = new_owner;
+        emit!(OwnershipTransferStarted { previous_owner: presale.owner, new_owner });
+        Ok(())
+    }
+
+    pub fn accept_ownership(ctx: Context<AcceptOwnership>) -> Result<()> {
+        let presale = &mut ctx.accounts.presale;
+        let previous_owner = presale.owner;
+        presale.owner = presale.pending_owner;
+        presale.pending_owner = Pubkey::default();
+        emit!(OwnershipTransferred { previous_owner, new_owner: presale.owner });
+        Ok(())
+    }
+
+    /// Recover tokens sent by mistake to presale-owned accounts; SNRG only once the sale is closed.
+    /// Refund escrows hold buyer funds and can't be drained; proceeds vault tokens are owned by
+    /// the vault PDA and are out of reach by construction
+    pub fn recover_token(ctx: Context<RecoverToken>, amount: u64) -> Result<()> {
+        require_gt!(amount, 0, PresaleError::ZeroAmount);
+
+        let presale = &ctx.accounts.presale;
+        let token = ctx.accounts.mint.key();
+        let (escrow, _) = Pubkey::find_program_address(&[b"escrow", presale.key().as_ref(), token.as_ref()], &crate::ID);
+        require_keys_neq!(ctx.accounts.source.key(), escrow, PresaleError::CannotRecoverEscrow);
+        if token == presale.snrg_mint {
+            let phase = _sale_phase(presale, Clock::get()?.unix_timestamp);
+            require!(
+                phase == SalePhase::Closed || phase == SalePhase::Ended,
+                PresaleError::CannotRecoverSnrg
+            );
+        }
+
+        let seeds = &[b"presale", presale.treasury.as_ref(), &[presale.bump]];
+        token::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.source.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: presale.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                },
+                &[&seeds[..]],
+            ),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+
+        emit!(TokenRecovered { token, amount });
+        Ok(())
+    }
+
+    /// Recover lamports held by the presale PDA above its rent-exempt minimum
+    pub fn recover_lamports(ctx: Context<RecoverLamports>) -> Result<()> {
+        let presale_info = ctx.accounts.presale.to_account_info();
+        let rent_minimum = Rent::get()?.minimum_balance(presale_info.data_len());
+        let amount = presale_info.lamports().saturating_sub(rent_minimum);
+        require_gt!(amount, 0, PresaleError::ZeroAmount);
+
+        **presale_info.try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.treasury.to_account_info().try_borrow_mut_lamports()? += amount;
+
+        emit!(LamportsRecovered { amount });
+        Ok(())
+    }
+
+    // View functions
+    pub fn get_remaining_purchases_today(ctx: Context<ViewTracking>) -> Result<u64> {
+        let now = Clock::get()?.unix_timestamp;
+        let t = &ctx.accounts.tracking;
+        if now >= t.daily_purchase, Ok(MAX_PURCHASES_PER_DAY)
+        } else if t.purchase_count_today >= MAX_PURCHASES_PER_DAY {
+            Ok(0)
+        } else {
+            Ok(MAX_PURCHASES_PER_DAY - t.purchase_count_today)
+        }
+    }
+
+    pub fn get_time_till_next_purchase(ctx: Context<ViewTracking>) -> Result<i64> {
+        let now = Clock::get()?.unix_timestamp;
+        let end = ctx.accounts.tracking.last_purchase_time + PURCHASE_COOLDOWN;
+        Ok(end.saturating_sub(now).max(0))
+    }
+
+    pub fn is_nonce_used(ctx: Context<ViewNonce>, nonce: u128) -> Result<bool> {
+        Ok(ctx.accounts.nonce_state.used_nonces.contains_key(&nonce))
+    }
+
+    pub fn get_sale_phase(ctx: Context<ViewPresale>) -> Result<SalePhase> {
+        Ok(_sale_phase(&ctx.accounts.presale, Clock::get()?.unix_timestamp))
+    }
+
+    pub fn get_auction_price(ctx: Context<ViewPresale>) -> Result<u64> {
+        let now = Clock::get()?.unix_timestamp;
+        _auction_price(&ctx.accounts.presale.auction, now)
+    }
+
+    pub fn get_sale_stats(ctx: Context<ViewStats>) -> Result<SaleStats> {
+        Ok((*ctx.accounts.stats).clone())
+    }
+}
+
+// Internal helpers
+fn _sale_phase(presale: &Presale, now: i64) -> SalePhase {
+    if presale.paused {
+        SalePhase::Paused
+    } else if presale.start_time != 0 && now < presale.start_time {
+        SalePhase::Upcoming
+    } else if presale.end_time != 0 && now >= presale.end_time {
+        SalePhase::Ended
+    } else if presale.open {
+        SalePhase::Live
+    } else {
+        SalePhase::Closed
+    }
+}
+
+fn _require_live(presale: &Presale) -> Result<()> {
+    match _sale_phase(presale, Clock::get()?.unix_timestamp) {
+        SalePhase::Live => Ok(()),
+        SalePhase::Paused => err!(PresaleError::Paused),
+        SalePhase::Upcoming => err!(PresaleError::SaleNotStarted),
+        SalePhase::Ended => err!(PresaleError::SaleEnded),
+        SalePhase::Closed => err!(PresaleError::PresaleClosed),
+    }
+}
+
+// `tracking` is the recipient's: cooldown and daily count follow whoever receives the SNRG
+fn _check_purchase_limits(presale: &Presale, tracking: &PurchaseTracking, snrg_amount: u64) -> Result<()> {
+    _check_purchase_amount(presale, snrg_amount)?;
+    _check_purchase_rate(tracking, Clock::get()?.unix_timestamp)
+}
+
+fn _check_purchase_amount(presale: &Presale, snrg_amount: u64) -> Result<()> {
+    require_gte!(snrg_amount, MIN_PURCHASE_AMOUNT, PresaleError::AmountTooLow);
+    require!(snrg_amount <= presale.max_purchase_amount, PresaleError::AmountTooHigh);
+    Ok(())
+}
+
+// Mirrors `_update_purchase_tracking`: the day window restarts 24h after its first purchase
+fn _check_purchase_rate(tracking: &PurchaseTracking, now: i64) -> Result<()> {
+    if tracking.last_purchase_time != 0 {
+        require!(now >= tracking.last_purchase_time + PURCHASE_COOLDOWN, PresaleError::PurchaseTooSoon);
+    }
+    if now < tracking.daily_reset + 86_400 {
+        require!(tracking.purchase_count_today < MAX_PURCHASES_PER_DAY, PresaleError::DailyLimitExceeded);
+    }
+    Ok(())
+}
+
+fn _build_message_hash(
+    buyer: Pubkey,
+    recipient: Pubkey,
+    referrer: Pubkey,
+    payment_token: Pubkey,
+    payment_amount: u64,
+    snrg_amount: u64,
+    nonce: u128,
+    deadline: i64,
+) -> Result<[u8; 32]> {
+    // Typed layout shared with the EVM presales (see `snrg_purchase_message`)
+    let domain = Domain::solana(crate::ID.to_bytes());
+    let purchase = Purchase {
+        buyer: buyer.to_bytes(),
+        recipient: recipient.to_bytes(),
+        referrer: referrer.to_bytes(), // signers bind the referral so a buyer can't add their own
+        payment_token: payment_token.to_bytes(),
+        payment_amount: payment_amount as u128,
+        snrg_amount: snrg_amount as u128,
+        nonce,
+        deadline: u64::try_from(deadline).map_err(|_| PresaleError::SignatureExpired)?,
+    };
+
+    Ok(purchase_digest(&domain, &purchase))
+}
+
+// Lamports per whole SNRG at `now`, decaying from start_price to floor_price over the duration
+fn _auction_price(auction: &DutchAuction, now: i64) -> Result<u64> {
+    require_gt!(auction.duration, 0, PresaleError::InvalidAuctionConfig);
+    if auction.clearing_price > 0 {
+        return Ok(auction.clearing_price);
+    }
+
+    let elapsed = now.saturating_sub(auction.start_time).clamp(0, auction.duration) as u128;
+    let duration = auction.duration as u128;
+    let span = (auction.start_price - auction.floor_price) as u128;
+    let remaining = duration - elapsed;
+
+    let premium = match auction.curve {
+        DecayCurve::Linear => span * remaining / duration,
+        // Falls quickly at first, then flattens towards the floor
+        DecayCurve::Quadratic => span
+            .checked_mul(remaining * remaining)
+            .ok_or(PresaleError::MathOverflow)?
+            / (duration * duration),
+    };
+
+    Ok(auction.floor_price + premium as u64)
+}
+
+fn _auction_ended(auction: &DutchAuction, now: i64) -> bool {
+    auction.ended_at != 0 || now >= auction.start_time.saturating_add(auction.duration)
+}
+
+// Rounds up so the escrowed payment always covers the SNRG at `price`
+fn _auction_cost(snrg_amount: u64, price: u64) -> Result<u64> {
+    let cost = (snrg_amount as u128)
+        .checked_mul(price as u128)
+        .ok_or(PresaleError::MathOverflow)?
+        .div_ceil(SNRG_UNIT as u128);
+    u64::try_from(cost).map_err(|_| error!(PresaleError::MathOverflow))
+}
+
+fn _commitment_hash(
+    buyer: Pubkey,
+    recipient: Pubkey,
+    payment_amount: u64,
+    snrg_amount: u64,
+    nonce: u128,
+    deadline: i64,
+    salt: &[u8; 32],
+) -> [u8; 32] {
+    keccak::hashv(&[
+        buyer.as_ref(),
+        recipient.as_ref(),
+        &payment_amount.to_le_bytes(),
+        &snrg_amount.to_le_bytes(),
+        &nonce.to_le_bytes(),
+        &deadline.to_le_bytes(),
+        salt,
+    ])
+    .0
+}
+
+fn _validate_signer_set(signers: &[Pubkey], threshold: u8) -> Result<()> {
+    require!(!signers.is_empty() && signers.len() <= MAX_SIGNERS, PresaleError::InvalidSignerSet);
+    require!(threshold > 0 && (threshold as usize) <= signers.len(), PresaleError::InvalidThreshold);
+
+    for (i, signer) in signers.iter().enumerate() {
+        require_keys_neq!(*signer, Pubkey::default(), PresaleError::ZeroAddress);
+        require!(!signers[..i].contains(signer), PresaleError::DuplicateSigner);
+    }
+    Ok(())
+}
+
+// Requires `threshold` valid signatures from distinct members of the signer set
+fn _verify_signatures(
+    presale: &Presale,
+    message_hash: [u8; 32],
+    signatures: &[[u8; 65]],
+    buyer: Pubkey,
+    nonce: u128,
+    nonce_state: &mut Account<NonceState>,
+) -> Result<()> {
+    require!(nonce > 0 && nonce <= u128::from(u128::MAX), PresaleError::InvalidNonce);
+
+    if nonce_state.used_nonces.contains_key(&nonce) {
+        return err!(PresaleError::NonceAlreadyUsed);
+    }
+
+    require!(signatures.len() <= presale.signers.len(), PresaleError::InvalidSignature);
+
+    let mut approved: Vec<Pubkey> = Vec::with_capacity(signatures.len());
+    for signature in signatures {
+        // Accept both raw (0/1) and EVM-style (27/28) recovery ids
+        let recovery_id = if signature[64] >= 27 { signature[64] - 27 } else { signature[64] };
+        let recovered = solana_program::secp256k1_recover::secp256k1_recover(&message_hash, recovery_id, &signature[..64])
+            .map_err(|_| PresaleError::InvalidSignature)?;
+        let pubkey = Pubkey::new_from_array(signer_id(&recovered.to_bytes()));
+
+        require!(presale.signers.contains(&pubkey), PresaleError::InvalidSignature);
+        require!(!approved.contains(&pubkey), PresaleError::DuplicateSigner);
+        approved.push(pubkey);
+    }
+
+    require!(approved.len() >= presale.threshold as usize, PresaleError::ThresholdNotMet);
+
+    nonce_state.used_nonces.insert(nonce, true);
+    emit!(SignatureVerified { buyer, nonce });
+
+    Ok(())
+}
+
+fn _deliver_snrg_exact(
+    treasury_token: &Account<TokenAccount>,
+    buyer_token: &mut Account<TokenAccount>,
+    treasury_signer: &UncheckedAccount,
+    presale: &Presale,
+    amount: u64,
+    token_program: &Program<Token>,
+) -> Result<()> {
+    let before_buyer = buyer_token.amount;
+    let before_treasury = treasury_token.amount;
+    require!(before_treasury >= amount, PresaleError::InsufficientBalance);
+
+    let seeds = &[b"presale", presale.treasury.as_ref(), &[presale.bump]];
+    let signer = &[&seeds[..]];
+
+    token::transfer_checked(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: treasury_token.to_account_info(),
                 to: buyer_token.to_account_info(),
                 authority: treasury_signer.to_account_info(),
                 mint: treasury_token.mint.to_account_info(),
             },
-            signer,
+            signer,
+        ),
+        amount,
+        treasury_token.mint_decimals,
+    )?;
+
+    buyer_token.reload()?;
+    let received = buyer_token.amount - before_buyer;
+    require_eq!(received, amount, PresaleError::InexactDelivery);
+
+    Ok(())
+}
+
+// Pays the referrer (and optionally the buyer) a bonus in SNRG from the treasury and
+// returns the buyer's share. Referral accounts are optional; both must be supplied
+// together or not at all.
+fn _credit_referral(
+    referral: &mut Option<Account<Referral>>,
+    referrer_token: &mut Option<Account<TokenAccount>>,
+    recipient_token: &mut Account<TokenAccount>,
+    treasury_token: &Account<TokenAccount>,
+    treasury_signer: &UncheckedAccount,
+    presale: &Presale,
+    buyer: Pubkey,
+    recipient: Pubkey,
+    snrg_amount: u64,
+    defer_referrer: bool,
+    token_program: &Program<Token>,
+) -> Result<(u64, u64)> {
+    let (referral, referrer_token) = match (referral.as_mut(), referrer_token.as_mut()) {
+        (Some(referral), Some(referrer_token)) => (referral, referrer_token),
+        (None, None) => return Ok((0, 0)),
+        _ => return err!(PresaleError::InvalidReferrer),
+    };
+
+    // Anti-self-referral: the referrer wallet and its payout account must not be the buyer's
+    require_keys_neq!(referral.referrer, buyer, PresaleError::SelfReferral);
+    require_keys_neq!(referral.referrer, recipient, PresaleError::SelfReferral);
+    require_keys_eq!(referrer_token.owner, referral.referrer, PresaleError::InvalidReferrer);
+    require_keys_neq!(referrer_token.key(), recipient_token.key(), PresaleError::SelfReferral);
+
+    let referrer_bonus = _bps_of(snrg_amount, presale.referral_bonus_bps)?;
+    let buyer_bonus = _bps_of(snrg_amount, presale.buyer_referral_bonus_bps)?;
+
+    if buyer_bonus > 0 {
+        _deliver_snrg_exact(treasury_token, recipient_token, treasury_signer, presale, buyer_bonus, token_program)?;
+    }
+
+    // Refundable purchases hold the referrer bonus on the receipt until the window closes
+    if defer_referrer {
+        emit!(ReferralDeferred { referrer: referral.referrer, buyer: recipient, snrg_amount, referrer_bonus, buyer_bonus });
+        return Ok((buyer_bonus, referrer_bonus));
+    }
+
+    _pay_referrer(
+        referral,
+        referrer_token,
+        treasury_token,
+        treasury_signer,
+        presale,
+        recipient,
+        snrg_amount,
+        referrer_bonus,
+        buyer_bonus,
+        token_program,
+    )?;
+    Ok((buyer_bonus, 0))
+}
+
+// Pays the referrer bonus and books the referred purchase on the referral
+fn _pay_referrer(
+    referral: &mut Account<Referral>,
+    referrer_token: &mut Account<TokenAccount>,
+    treasury_token: &Account<TokenAccount>,
+    treasury_signer: &UncheckedAccount,
+    presale: &Presale,
+    buyer: Pubkey,
+    snrg_amount: u64,
+    referrer_bonus: u64,
+    buyer_bonus: u64,
+    token_program: &Program<Token>,
+) -> Result<()> {
+    if referrer_bonus > 0 {
+        _deliver_snrg_exact(treasury_token, referrer_token, treasury_signer, presale, referrer_bonus, token_program)?;
+    }
+
+    referral.referred_volume = referral.referred_volume.checked_add(snrg_amount).ok_or(PresaleError::MathOverflow)?;
+    referral.referred_purchases = referral.referred_purchases.checked_add(1).ok_or(PresaleError::MathOverflow)?;
+    referral.bonus_earned = referral.bonus_earned.checked_add(referrer_bonus).ok_or(PresaleError::MathOverflow)?;
+
+    emit!(ReferralCredited {
+        referrer: referral.referrer,
+        buyer,
+        snrg_amount,
+        referrer_bonus,
+        buyer_bonus,
+        referred_volume: referral.referred_volume,
+    });
+    Ok(())
+}
+
+// Stakes a purchase and its buyer referral bonus for the buyer via `snrg_staking::stake`. The SNRG
+// only passes through the buyer's account within this instruction. The presale stake bonus is an
+// annual rate scaled by the duration and funded straight from the treasury as staking reward, so
+// an early or emergency exit forfeits it.
+fn _stake_purchase<'info>(
+    staking_program: &Option<Program<'info, SnrgStaking>>,
+    staking: &Option<UncheckedAccount<'info>>,
+    user_stakes: &Option<UncheckedAccount<'info>>,
+    stake_position: &Option<UncheckedAccount<'info>>,
+    vault: &Option<UncheckedAccount<'info>>,
+    vault_authority: &Option<UncheckedAccount<'info>>,
+    snrg_mint: &Option<Account<'info, Mint>>,
+    buyer: &Signer<'info>,
+    buyer_token: &mut Account<'info, TokenAccount>,
+    treasury_token: &Account<'info, TokenAccount>,
+    treasury_signer: &UncheckedAccount<'info>,
+    presale: &Presale,
+    snrg_amount: u64,
+    referral_bonus: u64,
+    duration_days: u64,
+    token_program: &Program<'info, Token>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let (staking_program, staking, user_stakes, stake_position, vault, vault_authority, snrg_mint) =
+        match (staking_program, staking, user_stakes, stake_position, vault, vault_authority, snrg_mint) {
+            (Some(p), Some(s), Some(u), Some(sp), Some(v), Some(a), Some(m)) => (p, s, u, sp, v, a, m),
+            _ => return err!(PresaleError::MissingStakingAccounts),
+        };
+
+    let stake_bonus = _stake_bonus(snrg_amount, presale.stake_bonus_bps, duration_days)?;
+    let amount = snrg_amount.checked_add(referral_bonus).ok_or(PresaleError::MathOverflow)?;
+
+    // The presale PDA signs as delegate over the treasury SNRG account for the bonus
+    let (bonus_funder, bonus_source) = if stake_bonus > 0 {
+        (Some(treasury_signer.to_account_info()), Some(treasury_token.to_account_info()))
+    } else {
+        (None, None)
+    };
+    let seeds = &[b"presale", presale.treasury.as_ref(), &[presale.bump]];
+    let signer = &[&seeds[..]];
+    let cpi_ctx = CpiContext::new_with_signer(
+        staking_program.to_account_info(),
+        snrg_staking::cpi::accounts::StakeTokens {
+            staking: staking.to_account_info(),
+            user_stakes: user_stakes.to_account_info(),
+            stake_position: stake_position.to_account_info(),
+            user: buyer.to_account_info(),
+            user_token: buyer_token.to_account_info(),
+            vault: vault.to_account_info(),
+            vault_authority: vault_authority.to_account_info(),
+            mint: snrg_mint.to_account_info(),
+            partner_reward: None,
+            bonus_funder,
+            bonus_source,
+            token_program: token_program.to_account_info(),
+            system_program: system_program.to_account_info(),
+        },
+        signer,
+    );
+    if stake_bonus > 0 {
+        snrg_staking::cpi::stake_with_bonus(cpi_ctx, amount, duration_days, stake_bonus)?;
+    } else {
+        snrg_staking::cpi::stake(cpi_ctx, amount, duration_days)?;
+    }
+
+    emit!(PurchaseStaked { buyer: buyer.key(), amount, stake_bonus, duration_days });
+    Ok(())
+}
+
+// `bonus_bps` is an annual rate: a 365-day stake earns it in full, shorter stakes pro rata
+fn _stake_bonus(snrg_amount: u64, bonus_bps: u16, duration_days: u64) -> Result<u64> {
+    let bonus = (snrg_amount as u128)
+        .checked_mul(bonus_bps as u128)
+        .and_then(|v| v.checked_mul(duration_days as u128))
+        .ok_or(PresaleError::MathOverflow)?
+        / (BPS_DENOMINATOR as u128 * 365);
+    u64::try_from(bonus).map_err(|_| error!(PresaleError::MathOverflow))
+}
+
+fn _open_receipt(
+    receipt: &mut Account<PurchaseReceipt>,
+    presale: &Presale,
+    buyer: Pubkey,
+    recipient: Pubkey,
+    payment_token: Pubkey,
+    paid_amount: u64,
+    snrg_amount: u64,
+    nonce: u128,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    receipt.buyer = buyer;
+    receipt.recipient = recipient;
+    receipt.nonce = nonce;
+    receipt.payment_token = payment_token;
+    receipt.paid_amount = paid_amount;
+    receipt.snrg_amount = snrg_amount;
+    receipt.refundable_until = now.checked_add(presale.refund_window).ok_or(PresaleError::MathOverflow)?;
+
+    emit!(ReceiptOpened {
+        buyer,
+        nonce,
+        paid_amount,
+        refundable_until: receipt.refundable_until,
+    });
+    Ok(())
+}
+
+// Moves a token receipt's escrowed payment out of the presale escrow, signed by the presale PDA
+fn _release_escrowed_tokens(
+    escrow: &Option<Account<TokenAccount>>,
+    destination: &Option<Account<TokenAccount>>,
+    payment_mint: &Option<Account<Mint>>,
+    presale: &Account<Presale>,
+    receipt: &PurchaseReceipt,
+    token_program: &Program<Token>,
+) -> Result<()> {
+    let (escrow, destination, payment_mint) = match (escrow, destination, payment_mint) {
+        (Some(escrow), Some(destination), Some(payment_mint)) => (escrow, destination, payment_mint),
+        _ => return err!(PresaleError::InvalidReceipt),
+    };
+    require_keys_eq!(payment_mint.key(), receipt.payment_token, PresaleError::InvalidReceipt);
+
+    let seeds = &[b"presale", presale.treasury.as_ref(), &[presale.bump]];
+    token::transfer_checked(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: escrow.to_account_info(),
+                to: destination.to_account_info(),
+                authority: presale.to_account_info(),
+                mint: payment_mint.to_account_info(),
+            },
+            &[&seeds[..]],
         ),
-        amount,
-        treasury_token.mint_decimals,
-    )?;
+        receipt.paid_amount,
+        payment_mint.decimals,
+    )
+}
+
+// Where settled native proceeds go: the SOL proceeds vault while milestones gate them, else the treasury
+fn _native_proceeds_destination<'info>(
+    presale: &Presale,
+    treasury: AccountInfo<'info>,
+    proceeds_vault: &mut Option<Account<'info, ProceedsVault>>,
+    amount: u64,
+) -> Result<AccountInfo<'info>> {
+    match proceeds_vault.as_mut() {
+        Some(vault) if presale.proceeds_escrow => {
+            require_keys_eq!(vault.payment_token, Pubkey::default(), PresaleError::InvalidProceedsVault);
+            vault.deposited = vault.deposited.checked_add(amount).ok_or(PresaleError::MathOverflow)?;
+            Ok(vault.to_account_info())
+        }
+        None if !presale.proceeds_escrow => Ok(treasury),
+        _ => err!(PresaleError::InvalidProceedsVault),
+    }
+}
+
+// Portion of a vault's deposits unlocked at `now`, and how many milestones have been reached.
+// Reaching the final milestone unlocks everything deposited, including later deposits.
+fn _unlocked_proceeds(schedule: &MilestoneSchedule, vault: &ProceedsVault, now: i64) -> Result<(u64, u8)> {
+    let reached = schedule.milestones.iter().take_while(|m| m.unlock_time <= now).count();
+    if reached == schedule.milestones.len() {
+        return Ok((vault.deposited, reached as u8));
+    }
+
+    // configure_milestones checked the full sum, so a prefix can't overflow
+    let cumulative: u64 = schedule.milestones[..reached].iter().map(|m| m.value).sum();
+    let unlocked = match schedule.kind {
+        MilestoneKind::Percentage => _bps_of(vault.deposited, cumulative as u16)?,
+        MilestoneKind::Amount(mint) if mint == vault.payment_token => cumulative.min(vault.deposited),
+        MilestoneKind::Amount(_) => 0,
+    };
+    Ok((unlocked, reached as u8))
+}
+
+fn _bps_of(amount: u64, bps: u16) -> Result<u64> {
+    let value = (amount as u128)
+        .checked_mul(bps as u128)
+        .ok_or(PresaleError::MathOverflow)?
+        / BPS_DENOMINATOR as u128;
+    Ok(value as u64)
+}
+
+fn _record_sale(
+    stats: &mut Account<SaleStats>,
+    payment_token: Pubkey,
+    paid_amount: u64,
+    snrg_amount: u64,
+    first_purchase: bool,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+
+    stats.total_snrg_sold = stats.total_snrg_sold.checked_add(snrg_amount).ok_or(PresaleError::MathOverflow)?;
+    let raised = stats.total_raised.entry(payment_token).or_insert(0);
+    *raised = raised.checked_add(paid_amount).ok_or(PresaleError::MathOverflow)?;
+    require!(stats.total_raised.len() <= MAX_STATS_PAYMENT_MINTS, PresaleError::TooManyPaymentMints);
+
+    if first_purchase {
+        stats.unique_buyers = stats.unique_buyers.checked_add(1).ok_or(PresaleError::MathOverflow)?;
+    }
+    stats.purchases = stats.purchases.checked_add(1).ok_or(PresaleError::MathOverflow)?;
+    if stats.first_purchase_at == 0 {
+        stats.first_purchase_at = now;
+    }
+    stats.last_purchase_at = now;
+
+    Ok(())
+}
+
+fn _update_purchase_tracking(tracking: &mut Account<PurchaseTracking>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    tracking.last_purchase_time = now;
+
+    if now >= tracking.daily_reset + 86_400 {
+        tracking.purchase_count_today = 1;
+        tracking.daily_reset = now;
+    } else {
+        tracking.purchase_count_today += 1;
+    }
+
+    emit!(PurchaseTrackingUpdated {
+        buyer: tracking.buyer,
+        purchase_count: tracking.purchase_count_today,
+        reset_time: tracking.daily_reset,
+    });
+
+    Ok(())
+}
+
+// Accounts & Events (exact match to Solidity)
+#[account]
+pub struct Presale {
+    pub owner: Pubkey, // sole admin: signer sets, sale settings, pause and recovery
+    pub pending_owner: Pubkey, // nominated by `transfer_ownership`; default = none
+    pub snrg_mint: Pubkey,
+    pub treasury: Pubkey,
+    pub signers: Vec<Pubkey>, // EVM signer addresses, left-padded to 32 bytes
+    pub threshold: u8,
+    pub pending_signers: Vec<Pubkey>,
+    pub pending_threshold: u8,
+    pub signer_set_proposed_at: i64,
+    pub open: bool,
+    pub paused: bool,
+    pub max_purchase_amount: u64,
+    pub supported_tokens: BTreeMap<Pubkey, bool>,
+    pub referral_bonus_bps: u16,
+    pub buyer_referral_bonus_bps: u16,
+    pub sale_mode: SaleMode,
+    pub auction: DutchAuction,
+    pub refund_window: i64, // seconds; 0 = no withdrawal right
+    pub start_time: i64,    // 0 = unscheduled, `open` alone decides
+    pub end_time: i64,      // 0 = no scheduled end
+    pub proceeds_escrow: bool, // payments settle into proceeds vaults, released by milestone
+    pub stake_bonus_bps: u16,  // annual staking reward bonus for buyers who stake at purchase
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SalePhase {
+    Upcoming,
+    Live,
+    Closed, // `open` is false: not yet opened, or closed manually via `set_open`
+    Ended,
+    Paused,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SaleMode {
+    FixedPrice,
+    DutchAuction,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DecayCurve {
+    Linear,
+    Quadratic,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct DutchAuction {
+    pub start_price: u64, // lamports per whole SNRG
+    pub floor_price: u64,
+    pub start_time: i64,
+    pub duration: i64,
+    pub curve: DecayCurve,
+    pub supply: u64,
+    pub total_bid: u64,
+    pub clearing_price: u64, // set when the auction sells out
+    pub ended_at: i64,
+    pub open_bids: u64, // unclaimed bids; the next auction waits for these
+}
+
+impl Default for DutchAuction {
+    fn default() -> Self {
+        Self {
+            start_price: 0,
+            floor_price: 0,
+            start_time: 0,
+            duration: 0,
+            curve: DecayCurve::Linear,
+            supply: 0,
+            total_bid: 0,
+            clearing_price: 0,
+            ended_at: 0,
+            open_bids: 0,
+        }
+    }
+}
+
+#[account]
+pub struct PurchaseTracking {
+    pub buyer: Pubkey,
+    pub last_purchase_time: i64,
+    pub purchase_count_today: u64,
+    pub daily_reset: i64,
+}
+
+#[account]
+pub struct NonceState {
+    pub buyer: Pubkey,
+    pub used_nonces: BTreeMap<u128, bool>,
+}
+
+#[account]
+pub struct SaleStats {
+    pub presale: Pubkey,
+    pub total_snrg_sold: u64,
+    pub total_raised: BTreeMap<Pubkey, u64>, // payment mint (default = native SOL) → amount
+    pub unique_buyers: u64,
+    pub purchases: u64,
+    pub first_purchase_at: i64,
+    pub last_purchase_at: i64,
+    pub bump: u8,
+}
+
+#[account]
+pub struct PurchaseCommitment {
+    pub buyer: Pubkey,
+    pub commitment: [u8; 32],
+    pub deposit: u64,
+    pub committed_slot: u64,
+    pub committed_at: i64,
+    pub bump: u8,
+}
+
+#[account]
+pub struct MilestoneSchedule {
+    pub presale: Pubkey,
+    pub kind: MilestoneKind,
+    pub milestones: Vec<Milestone>,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum MilestoneKind {
+    Percentage,     // `value` in bps of each vault's deposits, summing to 10_000
+    Amount(Pubkey), // `value` in units of this payment mint (default = SOL)
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct Milestone {
+    pub unlock_time: i64,
+    pub value: u64, // unlocked by this milestone, in the schedule's `kind`
+}
+
+#[account]
+pub struct ProceedsVault {
+    pub presale: Pubkey,
+    pub payment_token: Pubkey, // default = native SOL, held in this account
+    pub deposited: u64,
+    pub released: u64,
+    pub bump: u8,
+}
+
+#[account]
+pub struct PurchaseReceipt {
+    pub buyer: Pubkey, // payer, refunded on withdrawal
+    pub recipient: Pubkey, // holder of the SNRG, exercises the withdrawal right
+    pub nonce: u128,
+    pub payment_token: Pubkey, // default = native SOL, escrowed in this account
+    pub paid_amount: u64,
+    pub snrg_amount: u64, // SNRG the buyer must return to be refunded
+    pub refundable_until: i64,
+    pub referrer: Pubkey, // default = no referral
+    pub referrer_bonus: u64, // paid on sweep, dropped on refund
+    pub referred_amount: u64,
+}
+
+#[account]
+pub struct AuctionBid {
+    pub bidder: Pubkey,
+    pub snrg_amount: u64,
+    pub paid: u64, // lamports escrowed in this account
+    pub bump: u8,
+}
+
+#[account]
+pub struct Referral {
+    pub presale: Pubkey,
+    pub referrer: Pubkey,
+    pub referred_volume: u64,
+    pub referred_purchases: u64,
+    pub bonus_earned: u64,
+    pub bump: u8,
+}
+
+// Contexts & Events & Errors — identical to Solidity
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub snrg_mint: Account<'info, Mint>,
+    /// The presale PDA is keyed by treasury, so the treasury co-signs to keep anyone else from
+    /// initializing (and owning) its presale first
+    pub treasury: Signer<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 32 + 32 + 32 + 32 + (4 + MAX_SIGNERS * 32) + 1 + (4 + MAX_SIGNERS * 32) + 1 + 8
+            + 1 + 1 + 8 + 200 + 2 + 2 + 1 + (8 + 8 + 8 + 8 + 1 + 8 + 8 + 8 + 8 + 8) + 8 + 8 + 8 + 1 + 2 + 1,
+        seeds = [b"presale", treasury.key().as_ref()],
+        bump
+    )]
+    pub presale: Account<'info, Presale>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 32 + 8 + 4 + MAX_STATS_PAYMENT_MINTS * (32 + 8) + 8 + 8 + 8 + 8 + 1,
+        seeds = [b"stats", presale.key().as_ref()],
+        bump
+    )]
+    pub stats: Account<'info, SaleStats>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptOwnership<'info> {
+    #[account(mut, constraint = presale.pending_owner == pending_owner.key() @ PresaleError::NotPendingOwner)]
+    pub presale: Account<'info, Presale>,
+    pub pending_owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Admin<'info> {
+    #[account(mut, has_one = owner, has_one = treasury)]
+    pub presale: Account<'info, Presale>,
+    pub owner: Signer<'info>,
+    /// CHECK: treasury
+    pub treasury: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterReferrer<'info> {
+    #[account(mut)]
+    pub referrer: Signer<'info>,
+    pub presale: Account<'info, Presale>,
+    #[account(
+        init,
+        payer = referrer,
+        space = 8 + 32 + 32 + 8 + 8 + 8 + 1,
+        seeds = [b"referral", presale.key().as_ref(), referrer.key().as_ref()],
+        bump
+    )]
+    pub referral: Account<'info, Referral>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(payment_amount: u64, snrg_amount: u64, nonce: u128)]
+pub struct BuyWithNative<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    #[account(has_one = treasury)]
+    pub presale: Account<'info, Presale>,
+    /// CHECK: treasury wallet
+    #[account(mut)]
+    pub treasury: UncheckedAccount<'info>,
+    #[account(mut, token::mint = presale.snrg_mint)]
+    pub treasury_snrgtoken: Account<'info, TokenAccount>,
+    /// CHECK: SNRG recipient, bound by the signed message
+    pub recipient: UncheckedAccount<'info>,
+    #[account(mut, token::mint = presale.snrg_mint, token::authority = recipient)]
+    pub recipient_snrgtoken: Account<'info, TokenAccount>,
+    /// CHECK: presale PDA, delegate over the treasury SNRG account
+    #[account(seeds = [b"presale", presale.treasury.as_ref()], bump = presale.bump)]
+    pub treasury_signer: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"tracking", recipient.key().as_ref()], bump)]
+    pub tracking: Account<'info, PurchaseTracking>,
+    #[account(mut, seeds = [b"nonces", buyer.key().as_ref()], bump)]
+    pub nonce_state: Account<'info, NonceState>,
+    #[account(mut, seeds = [b"stats", presale.key().as_ref()], bump = stats.bump)]
+    pub stats: Account<'info, SaleStats>,
+    #[account(
+        mut,
+        seeds = [b"referral", presale.key().as_ref(), referral.referrer.as_ref()],
+        bump = referral.bump
+    )]
+    pub referral: Option<Account<'info, Referral>>,
+    #[account(mut, token::mint = presale.snrg_mint)]
+    pub referrer_snrgtoken: Option<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"proceeds", presale.key().as_ref(), Pubkey::default().as_ref()],
+        bump = proceeds_vault.bump
+    )]
+    pub proceeds_vault: Option<Account<'info, ProceedsVault>>,
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + 32 + 32 + 16 + 32 + 8 + 8 + 8 + 32 + 8 + 8,
+        seeds = [b"receipt", presale.key().as_ref(), buyer.key().as_ref(), &nonce.to_le_bytes()],
+        bump
+    )]
+    pub receipt: Option<Account<'info, PurchaseReceipt>>,
+    pub staking_program: Option<Program<'info, SnrgStaking>>,
+    /// CHECK: validated by the staking program
+    #[account(mut)]
+    pub staking: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated by the staking program
+    #[account(mut)]
+    pub user_stakes: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated by the staking program
+    #[account(mut)]
+    pub stake_position: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated by the staking program
+    #[account(mut)]
+    pub staking_vault: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated by the staking program
+    pub staking_vault_authority: Option<UncheckedAccount<'info>>,
+    #[account(address = presale.snrg_mint)]
+    pub snrg_mint: Option<Account<'info, Mint>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
 
-    buyer_token.reload()?;
-    let received = buyer_token.amount - before_buyer;
-    require_eq!(received, amount, PresaleError::InexactDelivery);
+#[derive(Accounts)]
+#[instruction(payment_amount: u64, snrg_amount: u64, nonce: u128)]
+pub struct BuyWithToken<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    #[account(has_one = treasury)]
+    pub presale: Account<'info, Presale>,
+    /// CHECK: treasury wallet
+    pub treasury: UncheckedAccount<'info>,
+    pub payment_mint: Account<'info, Mint>,
+    #[account(mut, token::mint = payment_mint, token::authority = buyer)]
+    pub buyer_payment_token: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = payment_mint, token::authority = treasury)]
+    pub treasury_payment_token: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = presale.snrg_mint)]
+    pub treasury_snrgtoken: Account<'info, TokenAccount>,
+    /// CHECK: SNRG recipient, bound by the signed message
+    pub recipient: UncheckedAccount<'info>,
+    #[account(mut, token::mint = presale.snrg_mint, token::authority = recipient)]
+    pub recipient_snrgtoken: Account<'info, TokenAccount>,
+    /// CHECK: presale PDA, delegate over the treasury SNRG account
+    #[account(seeds = [b"presale", presale.treasury.as_ref()], bump = presale.bump)]
+    pub treasury_signer: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"tracking", recipient.key().as_ref()], bump)]
+    pub tracking: Account<'info, PurchaseTracking>,
+    #[account(mut, seeds = [b"nonces", buyer.key().as_ref()], bump)]
+    pub nonce_state: Account<'info, NonceState>,
+    #[account(mut, seeds = [b"stats", presale.key().as_ref()], bump = stats.bump)]
+    pub stats: Account<'info, SaleStats>,
+    #[account(
+        mut,
+        seeds = [b"referral", presale.key().as_ref(), referral.referrer.as_ref()],
+        bump = referral.bump
+    )]
+    pub referral: Option<Account<'info, Referral>>,
+    #[account(mut, token::mint = presale.snrg_mint)]
+    pub referrer_snrgtoken: Option<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"proceeds", presale.key().as_ref(), payment_mint.key().as_ref()],
+        bump = proceeds_vault.bump
+    )]
+    pub proceeds_vault: Option<Account<'info, ProceedsVault>>,
+    #[account(mut, token::mint = payment_mint, token::authority = proceeds_vault)]
+    pub proceeds_vault_token: Option<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + 32 + 32 + 16 + 32 + 8 + 8 + 8 + 32 + 8 + 8,
+        seeds = [b"receipt", presale.key().as_ref(), buyer.key().as_ref(), &nonce.to_le_bytes()],
+        bump
+    )]
+    pub receipt: Option<Account<'info, PurchaseReceipt>>,
+    #[account(
+        mut,
+        seeds = [b"escrow", presale.key().as_ref(), payment_mint.key().as_ref()],
+        bump,
+        token::mint = payment_mint,
+        token::authority = presale
+    )]
+    pub escrow_payment_token: Option<Account<'info, TokenAccount>>,
+    pub staking_program: Option<Program<'info, SnrgStaking>>,
+    /// CHECK: validated by the staking program
+    #[account(mut)]
+    pub staking: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated by the staking program
+    #[account(mut)]
+    pub user_stakes: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated by the staking program
+    #[account(mut)]
+    pub stake_position: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated by the staking program
+    #[account(mut)]
+    pub staking_vault: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated by the staking program
+    pub staking_vault_authority: Option<UncheckedAccount<'info>>,
+    #[account(address = presale.snrg_mint)]
+    pub snrg_mint: Option<Account<'info, Mint>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
 
-    Ok(())
+#[derive(Accounts)]
+pub struct CommitPurchase<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    pub presale: Account<'info, Presale>,
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + 32 + 32 + 8 + 8 + 8 + 1,
+        seeds = [b"commit", presale.key().as_ref(), buyer.key().as_ref()],
+        bump
+    )]
+    pub purchase_commitment: Account<'info, PurchaseCommitment>,
+    pub system_program: Program<'info, System>,
 }
 
-fn _update_purchase_tracking(tracking: &mut Account<PurchaseTracking>) -> Result<()> {
-    let now = Clock::get()?.unix_timestamp;
-    tracking.last_purchase_time = now;
+#[derive(Accounts)]
+#[instruction(snrg_amount: u64, nonce: u128)]
+pub struct RevealPurchase<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    #[account(has_one = treasury)]
+    pub presale: Account<'info, Presale>,
+    /// CHECK: treasury wallet
+    #[account(mut)]
+    pub treasury: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = buyer,
+        has_one = buyer,
+        seeds = [b"commit", presale.key().as_ref(), buyer.key().as_ref()],
+        bump = purchase_commitment.bump
+    )]
+    pub purchase_commitment: Account<'info, PurchaseCommitment>,
+    #[account(mut, token::mint = presale.snrg_mint)]
+    pub treasury_snrgtoken: Account<'info, TokenAccount>,
+    /// CHECK: SNRG recipient, bound by the signed message
+    pub recipient: UncheckedAccount<'info>,
+    #[account(mut, token::mint = presale.snrg_mint, token::authority = recipient)]
+    pub recipient_snrgtoken: Account<'info, TokenAccount>,
+    /// CHECK: presale PDA, delegate over the treasury SNRG account
+    #[account(seeds = [b"presale", presale.treasury.as_ref()], bump = presale.bump)]
+    pub treasury_signer: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"tracking", recipient.key().as_ref()], bump)]
+    pub tracking: Account<'info, PurchaseTracking>,
+    #[account(mut, seeds = [b"nonces", buyer.key().as_ref()], bump)]
+    pub nonce_state: Account<'info, NonceState>,
+    #[account(mut, seeds = [b"stats", presale.key().as_ref()], bump = stats.bump)]
+    pub stats: Account<'info, SaleStats>,
+    #[account(
+        mut,
+        seeds = [b"referral", presale.key().as_ref(), referral.referrer.as_ref()],
+        bump = referral.bump
+    )]
+    pub referral: Option<Account<'info, Referral>>,
+    #[account(mut, token::mint = presale.snrg_mint)]
+    pub referrer_snrgtoken: Option<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"proceeds", presale.key().as_ref(), Pubkey::default().as_ref()],
+        bump = proceeds_vault.bump
+    )]
+    pub proceeds_vault: Option<Account<'info, ProceedsVault>>,
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + 32 + 32 + 16 + 32 + 8 + 8 + 8 + 32 + 8 + 8,
+        seeds = [b"receipt", presale.key().as_ref(), buyer.key().as_ref(), &nonce.to_le_bytes()],
+        bump
+    )]
+    pub receipt: Option<Account<'info, PurchaseReceipt>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
 
-    if now >= tracking.daily_reset + 86_400 {
-        tracking.purchase_count_today = 1;
-        tracking.daily_reset = now;
-    } else {
-        tracking.purchase_count_today += 1;
-    }
+#[derive(Accounts)]
+pub struct RefundCommitment<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    pub presale: Account<'info, Presale>,
+    #[account(
+        mut,
+        close = buyer,
+        has_one = buyer,
+        seeds = [b"commit", presale.key().as_ref(), buyer.key().as_ref()],
+        bump = purchase_commitment.bump
+    )]
+    pub purchase_commitment: Account<'info, PurchaseCommitment>,
+}
 
-    emit!(PurchaseTrackingUpdated {
-        buyer: tracking.buyer,
-        purchase_count: tracking.purchase_count_today,
-        reset_time: tracking.daily_reset,
-    });
+#[derive(Accounts)]
+pub struct PlaceBid<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    #[account(mut)]
+    pub presale: Account<'info, Presale>,
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        space = 8 + 32 + 8 + 8 + 1,
+        seeds = [b"bid", presale.key().as_ref(), bidder.key().as_ref()],
+        bump
+    )]
+    pub bid: Account<'info, AuctionBid>,
+    #[account(mut, seeds = [b"nonces", bidder.key().as_ref()], bump)]
+    pub nonce_state: Account<'info, NonceState>,
+    pub system_program: Program<'info, System>,
+}
 
-    Ok(())
+#[derive(Accounts)]
+pub struct ClaimAuction<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    #[account(mut, has_one = treasury)]
+    pub presale: Account<'info, Presale>,
+    /// CHECK: treasury wallet
+    #[account(mut)]
+    pub treasury: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = bidder,
+        has_one = bidder,
+        seeds = [b"bid", presale.key().as_ref(), bidder.key().as_ref()],
+        bump = bid.bump
+    )]
+    pub bid: Account<'info, AuctionBid>,
+    #[account(mut, token::mint = presale.snrg_mint)]
+    pub treasury_snrgtoken: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = presale.snrg_mint, token::authority = bidder)]
+    pub bidder_snrgtoken: Account<'info, TokenAccount>,
+    /// CHECK: presale PDA, delegate over the treasury SNRG account
+    #[account(seeds = [b"presale", presale.treasury.as_ref()], bump = presale.bump)]
+    pub treasury_signer: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"proceeds", presale.key().as_ref(), Pubkey::default().as_ref()],
+        bump = proceeds_vault.bump
+    )]
+    pub proceeds_vault: Option<Account<'info, ProceedsVault>>,
+    #[account(mut, seeds = [b"tracking", bidder.key().as_ref()], bump)]
+    pub tracking: Account<'info, PurchaseTracking>,
+    #[account(mut, seeds = [b"stats", presale.key().as_ref()], bump = stats.bump)]
+    pub stats: Account<'info, SaleStats>,
+    pub token_program: Program<'info, Token>,
 }
 
-// Accounts & Events (exact match to Solidity)
-#[account]
-pub struct Presale {
-    pub snrg_mint: Pubkey,
-    pub treasury: Pubkey,
-    pub signer: Pubkey,
-    pub open: bool,
-    pub paused: bool,
-    pub max_purchase_amount: u64,
-    pub supported_tokens: BTreeMap<Pubkey, bool>,
-    pub bump: u8,
+#[derive(Accounts)]
+pub struct RefundPurchase<'info> {
+    pub recipient: Signer<'info>,
+    /// CHECK: original payer, receives the refund and the receipt rent
+    #[account(mut)]
+    pub buyer: UncheckedAccount<'info>,
+    #[account(has_one = treasury)]
+    pub presale: Account<'info, Presale>,
+    /// CHECK: treasury wallet
+    pub treasury: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = buyer,
+        has_one = buyer,
+        has_one = recipient,
+        seeds = [b"receipt", presale.key().as_ref(), buyer.key().as_ref(), &receipt.nonce.to_le_bytes()],
+        bump
+    )]
+    pub receipt: Account<'info, PurchaseReceipt>,
+    #[account(address = presale.snrg_mint)]
+    pub snrg_mint: Account<'info, Mint>,
+    #[account(mut, token::mint = snrg_mint, token::authority = recipient)]
+    pub recipient_snrgtoken: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = snrg_mint, token::authority = treasury)]
+    pub treasury_snrgtoken: Account<'info, TokenAccount>,
+    pub payment_mint: Option<Account<'info, Mint>>,
+    #[account(
+        mut,
+        seeds = [b"escrow", presale.key().as_ref(), receipt.payment_token.as_ref()],
+        bump,
+        token::mint = payment_mint,
+        token::authority = presale
+    )]
+    pub escrow_payment_token: Option<Account<'info, TokenAccount>>,
+    #[account(mut, token::mint = payment_mint, token::authority = buyer)]
+    pub buyer_payment_token: Option<Account<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"stats", presale.key().as_ref()], bump = stats.bump)]
+    pub stats: Account<'info, SaleStats>,
+    pub token_program: Program<'info, Token>,
 }
 
-#[account]
-pub struct PurchaseTracking {
-    pub buyer: Pubkey,
-    pub last_purchase_time: i64,
-    pub purchase_count_today: u64,
-    pub daily_reset: i64,
+#[derive(Accounts)]
+pub struct SweepReceipt<'info> {
+    #[account(has_one = treasury)]
+    pub presale: Account<'info, Presale>,
+    /// CHECK: treasury wallet
+    #[account(mut)]
+    pub treasury: UncheckedAccount<'info>,
+    /// CHECK: original buyer, receives the receipt rent
+    #[account(mut, address = receipt.buyer)]
+    pub buyer: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = buyer,
+        seeds = [b"receipt", presale.key().as_ref(), receipt.buyer.as_ref(), &receipt.nonce.to_le_bytes()],
+        bump
+    )]
+    pub receipt: Account<'info, PurchaseReceipt>,
+    pub payment_mint: Option<Account<'info, Mint>>,
+    #[account(
+        mut,
+        seeds = [b"escrow", presale.key().as_ref(), receipt.payment_token.as_ref()],
+        bump,
+        token::mint = payment_mint,
+        token::authority = presale
+    )]
+    pub escrow_payment_token: Option<Account<'info, TokenAccount>>,
+    #[account(mut, token::mint = payment_mint, token::authority = treasury)]
+    pub treasury_payment_token: Option<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"proceeds", presale.key().as_ref(), receipt.payment_token.as_ref()],
+        bump = proceeds_vault.bump
+    )]
+    pub proceeds_vault: Option<Account<'info, ProceedsVault>>,
+    #[account(mut, token::mint = payment_mint, token::authority = proceeds_vault)]
+    pub proceeds_vault_token: Option<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"referral", presale.key().as_ref(), receipt.referrer.as_ref()],
+        bump = referral.bump
+    )]
+    pub referral: Option<Account<'info, Referral>>,
+    #[account(mut, token::mint = presale.snrg_mint)]
+    pub referrer_snrgtoken: Option<Account<'info, TokenAccount>>,
+    #[account(mut, token::mint = presale.snrg_mint)]
+    pub treasury_snrgtoken: Option<Account<'info, TokenAccount>>,
+    /// CHECK: presale PDA, delegate over the treasury SNRG account
+    #[account(seeds = [b"presale", presale.treasury.as_ref()], bump = presale.bump)]
+    pub treasury_signer: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
 }
 
-#[account]
-pub struct NonceState {
-    pub buyer: Pubkey,
-    pub used_nonces: BTreeMap<u128, bool>,
+#[derive(Accounts)]
+pub struct ConfigureMilestones<'info> {
+    #[account(mut, has_one = owner, has_one = treasury)]
+    pub presale: Account<'info, Presale>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    /// CHECK: treasury
+    pub treasury: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + 32 + (1 + 32) + 4 + MAX_MILESTONES * (8 + 8) + 1,
+        seeds = [b"milestones", presale.key().as_ref()],
+        bump
+    )]
+    pub milestone_schedule: Account<'info, MilestoneSchedule>,
+    pub system_program: Program<'info, System>,
 }
 
-// Contexts & Events & Errors — identical to Solidity
 #[derive(Accounts)]
-pub struct Initialize<'info> {
+pub struct OpenPaymentEscrow<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
-    pub snrg_mint: Account<'info, Mint>,
-    /// CHECK: treasury wallet
-    pub treasury: UncheckedAccount<'info>,
+    pub presale: Account<'info, Presale>,
+    pub payment_mint: Account<'info, Mint>,
     #[account(
         init,
         payer = payer,
-        space = 8 + 32 + 32 + 32 + 1 + 1 + 8 + 200 + 1,
-        seeds = [b"presale", treasury.key().as_ref()],
-        bump
+        seeds = [b"escrow", presale.key().as_ref(), payment_mint.key().as_ref()],
+        bump,
+        token::mint = payment_mint,
+        token::authority = presale
     )]
+    pub escrow_payment_token: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(payment_token: Pubkey)]
+pub struct OpenProceedsVault<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
     pub presale: Account<'info, Presale>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 32 + 32 + 8 + 8 + 1,
+        seeds = [b"proceeds", presale.key().as_ref(), payment_token.as_ref()],
+        bump
+    )]
+    pub proceeds_vault: Account<'info, ProceedsVault>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct Admin<'info> {
-    #[account(mut, has_one = treasury)]
+pub struct ReleaseProceeds<'info> {
+    #[account(has_one = treasury)]
+    pub presale: Account<'info, Presale>,
+    #[account(mut)]
+    pub treasury: Signer<'info>,
+    #[account(seeds = [b"milestones", presale.key().as_ref()], bump = milestone_schedule.bump)]
+    pub milestone_schedule: Account<'info, MilestoneSchedule>,
+    #[account(
+        mut,
+        seeds = [b"proceeds", presale.key().as_ref(), proceeds_vault.payment_token.as_ref()],
+        bump = proceeds_vault.bump
+    )]
+    pub proceeds_vault: Account<'info, ProceedsVault>,
+    pub payment_mint: Option<Account<'info, Mint>>,
+    #[account(mut, token::mint = payment_mint, token::authority = proceeds_vault)]
+    pub proceeds_vault_token: Option<Account<'info, TokenAccount>>,
+    #[account(mut, token::mint = payment_mint, token::authority = treasury)]
+    pub treasury_payment_token: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RecoverToken<'info> {
+    #[account(has_one = owner, has_one = treasury)]
+    pub presale: Account<'info, Presale>,
+    pub owner: Signer<'info>,
+    /// CHECK: treasury
+    pub treasury: UncheckedAccount<'info>,
+    pub mint: Account<'info, Mint>,
+    #[account(mut, token::mint = mint, token::authority = presale)]
+    pub source: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = mint, token::authority = treasury)]
+    pub destination: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RecoverLamports<'info> {
+    #[account(mut, has_one = owner, has_one = treasury)]
     pub presale: Account<'info, Presale>,
     pub owner: Signer<'info>,
     /// CHECK: treasury
+    #[account(mut)]
     pub treasury: UncheckedAccount<'info>,
 }
 
-// ... (BuyWithNative, BuyWithToken, View contexts — available on request if needed)
+#[derive(Accounts)]
+pub struct ViewPresale<'info> {
+    pub presale: Account<'info, Presale>,
+}
+
+#[derive(Accounts)]
+pub struct ViewStats<'info> {
+    pub presale: Account<'info, Presale>,
+    #[account(seeds = [b"stats", presale.key().as_ref()], bump = stats.bump)]
+    pub stats: Account<'info, SaleStats>,
+}
+
+// ... (ViewTracking, ViewNonce contexts — available on request if needed)
 
 #[event]
-pub struct PresaleInitialized { pub snrg_mint: Pubkey, pub treasury: Pubkey, pub signer: Pubkey }
+pub struct PresaleInitialized { pub snrg_mint: Pubkey, pub treasury: Pubkey, pub signers: Vec<Pubkey>, pub threshold: u8 }
+#[event]
+pub struct SignerSetProposed { pub signers: Vec<Pubkey>, pub threshold: u8, pub eta: i64 }
 #[event]
-pub struct SignerSet { pub old_signer: Pubkey, pub new_signer: Pubkey }
+pub struct SignerSetConfirmed { pub old_signers: Vec<Pubkey>, pub new_signers: Vec<Pubkey>, pub threshold: u8 }
+#[event]
+pub struct SignerSetCanceled { pub signers: Vec<Pubkey> }
 #[event]
 pub struct OpenSet { pub open: bool }
 #[event]
@@ -434,12 +2504,76 @@ pub struct MaxPurchaseAmountSet { pub amount: u64 }
 #[event]
 pub struct SupportedTokenSet { pub token: Pubkey, pub is_supported: bool }
 #[event]
-pub struct Purchased { pub buyer: Pubkey, pub payment_token: Pubkey, pub snrg_amount: u64, pub paid_amount: u64 }
+pub struct Purchased { pub buyer: Pubkey, pub recipient: Pubkey, pub payment_token: Pubkey, pub snrg_amount: u64, pub paid_amount: u64 }
 #[event]
 pub struct SignatureVerified { pub buyer: Pubkey, pub nonce: u128 }
 #[event]
 pub struct PurchaseTrackingUpdated { pub buyer: Pubkey, pub purchase_count: u64, pub reset_time: i64 }
 #[event]
+pub struct ReferralBonusSet { pub referrer_bps: u16, pub buyer_bps: u16 }
+#[event]
+pub struct ReferrerRegistered { pub referrer: Pubkey }
+#[event]
+pub struct ReferralCredited {
+    pub referrer: Pubkey,
+    pub buyer: Pubkey,
+    pub snrg_amount: u64,
+    pub referrer_bonus: u64,
+    pub buyer_bonus: u64,
+    pub referred_volume: u64,
+}
+#[event]
+pub struct ReferralDeferred { pub referrer: Pubkey, pub buyer: Pubkey, pub snrg_amount: u64, pub referrer_bonus: u64, pub buyer_bonus: u64 }
+#[event]
+pub struct PurchaseCommitted { pub buyer: Pubkey, pub commitment: [u8; 32], pub deposit: u64, pub slot: u64 }
+#[event]
+pub struct CommitmentRefunded { pub buyer: Pubkey, pub deposit: u64 }
+#[event]
+pub struct AuctionConfigured {
+    pub start_price: u64,
+    pub floor_price: u64,
+    pub start_time: i64,
+    pub duration: i64,
+    pub curve: DecayCurve,
+    pub supply: u64,
+}
+#[event]
+pub struct SaleModeSet { pub sale_mode: SaleMode }
+#[event]
+pub struct BidPlaced { pub bidder: Pubkey, pub snrg_amount: u64, pub price: u64, pub paid: u64 }
+#[event]
+pub struct AuctionSettled { pub bidder: Pubkey, pub snrg_amount: u64, pub clearing_price: u64, pub paid: u64, pub refund: u64 }
+#[event]
+pub struct ScheduleSet { pub start_time: i64, pub end_time: i64 }
+#[event]
+pub struct RefundWindowSet { pub refund_window: i64 }
+#[event]
+pub struct ReceiptOpened { pub buyer: Pubkey, pub nonce: u128, pub paid_amount: u64, pub refundable_until: i64 }
+#[event]
+pub struct PurchaseRefunded { pub buyer: Pubkey, pub payment_token: Pubkey, pub snrg_amount: u64, pub refunded_amount: u64 }
+#[event]
+pub struct ProceedsReleased { pub buyer: Pubkey, pub payment_token: Pubkey, pub amount: u64 }
+#[event]
+pub struct MilestonesConfigured { pub kind: MilestoneKind, pub milestones: Vec<Milestone> }
+#[event]
+pub struct PaymentEscrowOpened { pub payment_token: Pubkey, pub escrow: Pubkey }
+#[event]
+pub struct ProceedsVaultOpened { pub payment_token: Pubkey }
+#[event]
+pub struct MilestoneProceedsReleased { pub payment_token: Pubkey, pub amount: u64, pub milestones_reached: u8, pub total_released: u64 }
+#[event]
+pub struct StakeBonusSet { pub bonus_bps: u16 }
+#[event]
+pub struct PurchaseStaked { pub buyer: Pubkey, pub amount: u64, pub stake_bonus: u64, pub duration_days: u64 }
+#[event]
+pub struct TokenRecovered { pub token: Pubkey, pub amount: u64 }
+#[event]
+pub struct LamportsRecovered { pub amount: u64 }
+#[event]
+pub struct OwnershipTransferStarted { pub previous_owner: Pubkey, pub new_owner: Pubkey }
+#[event]
+pub struct OwnershipTransferred { pub previous_owner: Pubkey, pub new_owner: Pubkey }
+#[event]
 pub struct ContractPaused { pub caller: Pubkey }
 #[event]
 pub struct ContractUnpaused { pub caller: Pubkey }
@@ -450,4 +2584,120 @@ pub enum PresaleError {
     InvalidSignature, PurchaseTooSoon, DailyLimitExceeded, AmountTooLow, AmountTooHigh,
     InvalidNonce, InsufficientBalance, InexactDelivery, UnderpaidTreasury, SignatureExpired,
     Paused, NotPaused, AlreadyPaused, CannotUseSnrgAsPayment,
-}
\ No newline at end of file
+    BonusTooHigh, InvalidReferrer, SelfReferral, MathOverflow, CannotRecoverSnrg,
+    TooManyPaymentMints, InvalidSignerSet, InvalidThreshold, DuplicateSigner, ThresholdNotMet,
+    SignerSetPending, NoPendingSignerSet, RotationDelayNotElapsed,
+    RevealTooEarly, CommitmentExpired, CommitmentNotExpired, CommitmentMismatch,
+    WrongSaleMode, InvalidAuctionConfig, AuctionInProgress, AuctionNotStarted, AuctionEnded,
+    InvalidRefundWindow, InvalidReceipt, RefundWindowClosed, RefundWindowOpen,
+    InvalidSchedule, SaleNotStarted, SaleEnded,
+    MilestonesLocked, InvalidMilestones, InvalidProceedsVault, MilestoneNotReached, NothingToRelease,
+    StakeRequiresBuyer, StakeNotRefundable, MissingStakingAccounts, CannotRecoverEscrow,
+    BidExceedsAuthorized, NotPendingOwner,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auction(curve: DecayCurve) -> DutchAuction {
+        DutchAuction {
+            start_price: 1_000_000,
+            floor_price: 200_000,
+            start_time: 1_000,
+            duration: 100,
+            curve,
+            supply: 10 * SNRG_UNIT,
+            ..DutchAuction::default()
+        }
+    }
+
+    #[test]
+    fn auction_price_requires_configuration() {
+        assert!(_auction_price(&DutchAuction::default(), 0).is_err());
+    }
+
+    #[test]
+    fn linear_auction_price_decays_to_floor() {
+        let a = auction(DecayCurve::Linear);
+        assert_eq!(_auction_price(&a, 0).unwrap(), 1_000_000); // before start: start price
+        assert_eq!(_auction_price(&a, 1_000).unwrap(), 1_000_000);
+        assert_eq!(_auction_price(&a, 1_050).unwrap(), 600_000);
+        assert_eq!(_auction_price(&a, 1_100).unwrap(), 200_000);
+        assert_eq!(_auction_price(&a, 5_000).unwrap(), 200_000);
+    }
+
+    #[test]
+    fn quadratic_auction_price_falls_faster_early() {
+        let a = auction(DecayCurve::Quadratic);
+        assert_eq!(_auction_price(&a, 1_000).unwrap(), 1_000_000);
+        assert_eq!(_auction_price(&a, 1_050).unwrap(), 400_000);
+        assert!(_auction_price(&a, 1_050).unwrap() < _auction_price(&auction(DecayCurve::Linear), 1_050).unwrap());
+        assert_eq!(_auction_price(&a, 1_100).unwrap(), 200_000);
+    }
+
+    #[test]
+    fn sold_out_auction_settles_at_clearing_price() {
+        let a = DutchAuction { clearing_price: 750_000, ended_at: 1_020, ..auction(DecayCurve::Linear) };
+        assert_eq!(_auction_price(&a, 1_090).unwrap(), 750_000);
+        assert!(_auction_ended(&a, 1_021));
+    }
+
+    #[test]
+    fn stake_bonus_scales_with_duration() {
+        let amount = 1_000 * SNRG_UNIT;
+        assert_eq!(_stake_bonus(amount, 1_000, 365).unwrap(), 100 * SNRG_UNIT);
+        assert_eq!(_stake_bonus(amount, 1_000, 73).unwrap(), 20 * SNRG_UNIT);
+        assert_eq!(_stake_bonus(amount, 0, 365).unwrap(), 0);
+    }
+
+    #[test]
+    fn auction_cost_rounds_up() {
+        assert_eq!(_auction_cost(SNRG_UNIT, 600_000).unwrap(), 600_000);
+        assert_eq!(_auction_cost(1, 600_000).unwrap(), 1);
+        assert!(_auction_cost(u64::MAX, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn purchase_rate_enforces_cooldown_and_daily_count() {
+        let fresh = PurchaseTracking { buyer: Pubkey::default(), last_purchase_time: 0, purchase_count_today: 0, daily_reset: 0 };
+        _check_purchase_rate(&fresh, 1_000).unwrap();
+
+        let recent = PurchaseTracking { last_purchase_time: 10_000, purchase_count_today: 1, daily_reset: 10_000, ..fresh };
+        assert!(_check_purchase_rate(&recent, 10_000 + PURCHASE_COOLDOWN - 1).is_err());
+        _check_purchase_rate(&recent, 10_000 + PURCHASE_COOLDOWN).unwrap();
+
+        let busy = PurchaseTracking { purchase_count_today: MAX_PURCHASES_PER_DAY, ..recent };
+        assert!(_check_purchase_rate(&busy, 10_000 + PURCHASE_COOLDOWN).is_err());
+        _check_purchase_rate(&busy, 10_000 + 86_400).unwrap(); // a new day window
+    }
+
+    fn schedule(kind: MilestoneKind, values: [u64; 2]) -> MilestoneSchedule {
+        MilestoneSchedule {
+            presale: Pubkey::default(),
+            kind,
+            milestones: vec![
+                Milestone { unlock_time: 1_000, value: values[0] },
+                Milestone { unlock_time: 2_000, value: values[1] },
+            ],
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn milestones_unlock_by_percentage_or_amount() {
+        let usdc = Pubkey::new_unique();
+        let vault = |payment_token| ProceedsVault { presale: Pubkey::default(), payment_token, deposited: 10_000, released: 0, bump: 255 };
+
+        let pct = schedule(MilestoneKind::Percentage, [2_500, 7_500]);
+        assert_eq!(_unlocked_proceeds(&pct, &vault(usdc), 999).unwrap(), (0, 0));
+        assert_eq!(_unlocked_proceeds(&pct, &vault(usdc), 1_000).unwrap(), (2_500, 1));
+        assert_eq!(_unlocked_proceeds(&pct, &vault(usdc), 2_000).unwrap(), (10_000, 2));
+
+        let amount = schedule(MilestoneKind::Amount(usdc), [4_000, 50_000]);
+        assert_eq!(_unlocked_proceeds(&amount, &vault(usdc), 1_000).unwrap(), (4_000, 1));
+        // Other mints wait for the final milestone
+        assert_eq!(_unlocked_proceeds(&amount, &vault(Pubkey::default()), 1_000).unwrap(), (0, 1));
+        assert_eq!(_unlocked_proceeds(&amount, &vault(Pubkey::default()), 2_000).unwrap(), (10_000, 2));
+    }
+}
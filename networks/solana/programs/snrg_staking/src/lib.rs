@@ -1,11 +1,26 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token, TokenAccount, TransferChecked};
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::metadata::mpl_token_metadata::types::DataV2;
+use anchor_spl::metadata::{create_metadata_accounts_v3, CreateMetadataAccountsV3, Metadata};
+use anchor_spl::token::spl_token::instruction::AuthorityType;
+use anchor_spl::token::{Burn, CloseAccount, Mint, MintTo, SetAuthority, Token, TokenAccount, TransferChecked};
 
 declare_id!("YourSNRGStakingProgramID111111111111111111111111");
 
-pub const EARLY_WITHDRAWAL_FEE_BPS: u64 = 500;  // 5%
-pub const EMERGENCY_FEE_BPS: u64 = 1000;     // 10%
+pub const EARLY_WITHDRAWAL_FEE_BPS: u64 = 500;  // 5% (default)
+pub const EMERGENCY_FEE_BPS: u64 = 1000;     // 10% (default)
+pub const MAX_EARLY_WITHDRAWAL_FEE_BPS: u64 = 2_000; // 20%
+pub const MAX_EMERGENCY_FEE_BPS: u64 = 3_000;        // 30%
 pub const BPS_DENOMINATOR: u64 = 10_000;
+pub const MAX_REWARD_RATES: usize = 16;
+pub const MAX_PENDING_RATE_CHANGES: usize = 8;
+pub const MAX_REWARD_RATE_BPS: u64 = 5_000;         // 50%
+pub const MAX_RATE_CHANGE_DELAY: i64 = 30 * 86_400; // 30 days
+pub const FLEX_PRECISION: u128 = 1_000_000_000_000;  // reward-per-token scale
+pub const POSITION_NFT_SYMBOL: &str = "sSNRG";
+pub const MAX_POSITION_BASE_URI_LEN: usize = 128; // plus a base58 position key stays under Metaplex's 200
+pub const MAX_PARTNER_MINTS: usize = 4;
+pub const MAX_BATCH_WITHDRAWALS: u8 = 20;
 
 #[program]
 pub mod snrg_staking {
@@ -16,10 +31,38 @@ pub mod snrg_staking {
         staking.treasury = ctx.accounts.treasury.key();
         staking.snrg_mint = ctx.accounts.snrg_mint.key();
         staking.vault_authority = ctx.accounts.vault_authority.key();
+        staking.vault = ctx.accounts.vault.key();
+        staking.vault_authority_bump = ctx.bumps.vault_authority;
         staking.is_funded = false;
         staking.paused = false;
         staking.reward_reserve = 0;
         staking.promised_rewards = 0;
+        staking.rate_change_delay = 0;
+        staking.pending_rate_change_delay = 0;
+        staking.rate_change_delay_eta = 0;
+        staking.pending_rate_changes = Vec::new();
+        staking.early_withdrawal_fee_bps = EARLY_WITHDRAWAL_FEE_BPS;
+        staking.emergency_fee_bps = EMERGENCY_FEE_BPS;
+        staking.fee_burn_bps = 0;
+        staking.fee_treasury_bps = BPS_DENOMINATOR;
+        staking.fee_reserve_bps = 0;
+        staking.flex_reward_rate = 0;
+        staking.flex_total_staked = 0;
+        staking.flex_reward_per_token = 0;
+        staking.flex_last_update = 0;
+        staking.flex_promised = 0;
+        staking.flex_open_positions = 0;
+        staking.active_stakes = 0;
+        staking.early_reward_payout = std::collections::BTreeMap::new();
+        staking.tvl_cap = 0;
+        staking.max_user_principal = 0;
+        staking.total_term_staked = 0;
+        staking.tier_caps = std::collections::BTreeMap::new();
+        staking.tier_staked = std::collections::BTreeMap::new();
+        staking.emergency_mode = false;
+        staking.partner_mints = Vec::new();
+        staking.partner_tiers = std::collections::BTreeMap::new();
+        staking.position_base_uri = String::new();
         staking.bump = ctx.bumps.staking;
 
         // Default reward rates (duration in days → bps)
@@ -61,73 +104,110 @@ pub mod snrg_staking {
         Ok(())
     }
 
-    pub fn stake(ctx: Context<Stake>, amount: u64, duration_days: u64) -> Result<()> {
+    pub fn stake(ctx: Context<StakeTokens>, amount: u64, duration_days: u64) -> Result<()> {
+        _stake_tokens(ctx, amount, duration_days, 0)
+    }
+
+    /// Stakes with a funded bonus on top of the tier reward, e.g. a presale stake-at-purchase
+    /// bonus. The bonus pays out like reward: in full at maturity, under the tier's early payout
+    /// policy on early exit, never on an emergency exit.
+    pub fn stake_with_bonus(ctx: Context<StakeTokens>, amount: u64, duration_days: u64, bonus: u64) -> Result<()> {
+        require!(bonus > 0, StakingError::ZeroAmount);
+        _stake_tokens(ctx, amount, duration_days, bonus)
+    }
+
+    /// Funder stakes its own tokens for a beneficiary, e.g. locked reward or team grants.
+    /// With `locked`, the beneficiary cannot withdraw before maturity; only emergency mode, where
+    /// every staker may pull principal, overrides the lock.
+    pub fn stake_for(ctx: Context<StakeFor>, amount: u64, duration_days: u64, locked: bool) -> Result<()> {
         require!(amount > 0, StakingError::ZeroAmount);
         require!(duration_days > 0 && duration_days <= u64::from(u32::MAX), StakingError::InvalidDuration);
 
         let staking = &ctx.accounts.staking;
         require!(staking.is_funded, StakingError::NotFunded);
         require!(!staking.paused, StakingError::Paused);
+        require!(!staking.emergency_mode, StakingError::EmergencyMode);
 
-        let reward_bps = staking.reward_rates.get(&duration_days).ok_or(StakingError::InvalidDuration)?;
-        let reward = amount
-            .checked_mul(*reward_bps as u64)
-            .ok_or(StakingError::MathOverflow)?
-            .checked_div(BPS_DENOMINATOR)
-            .ok_or(StakingError::MathOverflow)?;
-
-        let required_reserve = staking.promised_rewards.checked_add(reward).ok_or(StakingError::MathOverflow)?;
-        if staking.reward_reserve < required_reserve {
-            emit!(ReserveShortfall { required: required_reserve, available: staking.reward_reserve });
-            return err!(StakingError::InsufficientReserves);
-        }
-
-        let now = Clock::get()?.unix_timestamp;
-        let end_time = now.checked_add((duration_days as i64) * 86_400).ok_or(StakingError::MathOverflow)?;
-
-        // Transfer principal to vault
-        let before = ctx.accounts.user_token.amount;
-        _transfer_to_vault_user_to_program(&ctx.accounts, amount)?;
-        let actual_received = ctx.accounts.user_token.reload()?.amount;
-        let actual_received = before - actual_received; // delta check
-
-        let stake = Stake {
-            amount: actual_received,
-            reward,
-            end_time,
-            withdrawn: false,
-        };
+        // Transfer principal from the funder to vault
+        let before = ctx.accounts.funder_token.amount;
+        token::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.funder_token.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.funder.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                },
+            ),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+        let actual_received = before - ctx.accounts.funder_token.reload()?.amount;
 
-        ctx.accounts.user_stakes.stakes.push(stake);
-        ctx.accounts.staking.promised_rewards = required_reserve;
+        let beneficiary = ctx.accounts.beneficiary.key();
+        let (stake_index, reward, end_time) = _open_stake(
+            &mut ctx.accounts.staking,
+            &mut ctx.accounts.user_stakes,
+            &mut ctx.accounts.stake_position,
+            beneficiary,
+            actual_received,
+            duration_days,
+            locked,
+        )?;
+        ctx.accounts.user_stakes.bump = ctx.bumps.user_stakes;
+        ctx.accounts.stake_position.bump = ctx.bumps.stake_position;
+        let snrg_decimals = ctx.accounts.mint.decimals;
+        _attach_partner_reward(&mut ctx.accounts.partner_reward, &mut ctx.accounts.stake_position, duration_days, snrg_decimals)?;
 
         emit!(Staked {
-            user: ctx.accounts.user.key(),
-            stake_index: ctx.accounts.user_stakes.stakes.len() - 1,
+            user: beneficiary,
+            stake_index,
             amount: actual_received,
             reward,
             end_time,
         });
+        emit!(StakedFor {
+            funder: ctx.accounts.funder.key(),
+            beneficiary,
+            stake_index,
+            locked,
+        });
 
         Ok(())
     }
 
+    /// Withdraws a matured stake; its position account is closed and the rent returned
     pub fn withdraw(ctx: Context<Withdraw>, stake_index: u64) -> Result<()> {
-        let user_stakes = &mut ctx.accounts.user_stakes;
-        let stake = user_stakes.stakes.get_mut(stake_index as usize).ok_or(StakingError::InvalidIndex)?;
-        require!(!stake.withdrawn, StakingError::AlreadyWithdrawn);
-
         let now = Clock::get()?.unix_timestamp;
-        require!(now >= stake.end_time, StakingError::StakeNotMatured);
+        require!(now >= ctx.accounts.stake_position.end_time, StakingError::StakeNotMatured);
+        _settle_partner_reward(ctx.accounts, stake_index, true)?;
+
+        let stake = &ctx.accounts.stake_position;
+        _redeem_position(
+            stake,
+            &ctx.accounts.user,
+            &ctx.accounts.position_mint,
+            &ctx.accounts.position_token,
+            &ctx.accounts.token_program,
+        )?;
 
-        stake.withdrawn = true;
         let total = stake.amount.checked_add(stake.reward).ok_or(StakingError::MathOverflow)?;
 
         let staking = &mut ctx.accounts.staking;
         staking.promised_rewards = staking.promised_rewards.checked_sub(stake.reward).ok_or(StakingError::MathOverflow)?;
         staking.reward_reserve = staking.reward_reserve.checked_sub(stake.reward).ok_or(StakingError::MathOverflow)?;
+        _close_stake_slot(
+            &mut ctx.accounts.staking,
+            &mut ctx.accounts.user_stakes,
+            stake.duration_days,
+            stake.amount,
+        )?;
 
         _transfer_from_vault(&ctx.accounts, total)?;
+        ctx.accounts.vault.reload()?;
+        let vault_balance = ctx.accounts.vault.amount;
+        _solvency_guard(&mut ctx.accounts.staking, vault_balance)?;
 
         emit!(Withdrawn {
             user: ctx.accounts.user.key(),
@@ -139,224 +219,2114 @@ pub mod snrg_staking {
         Ok(())
     }
 
-    pub fn withdraw_early(ctx: Context<WithdrawEarly>, stake_index: u64) -> Result<()> {
-        _withdraw_with_penalty(ctx, stake_index, EARLY_WITHDRAWAL_FEE_BPS, false)?
-    }
+    /// Withdraws up to `max_count` matured stakes, passed as stake position remaining accounts,
+    /// in one vault transfer. Tokenized positions and positions carrying a partner reward are
+    /// skipped and must go through `withdraw`.
+    pub fn withdraw_all_matured<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WithdrawAllMatured<'info>>,
+        max_count: u8,
+    ) -> Result<()> {
+        require!(max_count > 0 && max_count <= MAX_BATCH_WITHDRAWALS, StakingError::InvalidBatchSize);
+        let now = Clock::get()?.unix_timestamp;
+        let staking_key = ctx.accounts.staking.key();
+        let user = ctx.accounts.user.key();
 
-    pub fn emergency_withdraw(ctx: Context<EmergencyWithdraw>, stake_index: u64) -> Result<()> {
-        _withdraw_with_penalty(ctx, stake_index, EMERGENCY_FEE_BPS, true)?
-    }
+        let mut count: u64 = 0;
+        let mut total_amount: u64 = 0;
+        let mut total_reward: u64 = 0;
+        for info in ctx.remaining_accounts {
+            if count == max_count as u64 {
+                break;
+            }
+            // Already withdrawn (closed) positions are skipped
+            if info.owner != &crate::ID || info.data_is_empty() {
+                continue;
+            }
+            let stake = Account::<StakePosition>::try_from(info)?;
+            let expected = Pubkey::create_program_address(
+                &[b"stake", staking_key.as_ref(), user.as_ref(), &stake.index.to_le_bytes(), &[stake.bump]],
+                &crate::ID,
+            )
+            .map_err(|_| StakingError::InvalidStakePosition)?;
+            require_keys_eq!(expected, info.key(), StakingError::InvalidStakePosition);
+            if now < stake.end_time || stake.position_mint != Pubkey::default() || stake.partner_reward > 0 {
+                continue;
+            }
+
+            _close_stake_slot(
+                &mut ctx.accounts.staking,
+                &mut ctx.accounts.user_stakes,
+                stake.duration_days,
+                stake.amount,
+            )?;
+            total_amount = total_amount.checked_add(stake.amount).ok_or(StakingError::MathOverflow)?;
+            total_reward = total_reward.checked_add(stake.reward).ok_or(StakingError::MathOverflow)?;
+            count += 1;
+
+            emit!(Withdrawn {
+                user,
+                stake_index: stake.index,
+                amount: stake.amount,
+                reward: stake.reward,
+            });
+            stake.close(ctx.accounts.user.to_account_info())?;
+        }
+        require!(count > 0, StakingError::NothingToWithdraw);
 
-    // Admin
-    pub fn pause(ctx: Context<Admin>) -> Result<()> {
         let staking = &mut ctx.accounts.staking;
-        require!(!staking.paused, StakingError::AlreadyPaused);
-        staking.paused = true;
-        emit!(ContractPaused);
+        staking.promised_rewards = staking.promised_rewards.checked_sub(total_reward).ok_or(StakingError::MathOverflow)?;
+        staking.reward_reserve = staking.reward_reserve.checked_sub(total_reward).ok_or(StakingError::MathOverflow)?;
+
+        let total = total_amount.checked_add(total_reward).ok_or(StakingError::MathOverflow)?;
+        let seeds = &[b"vault_authority", staking_key.as_ref(), &[staking.vault_authority_bump]];
+        token::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault.to_account_info(),
+                    to: ctx.accounts.user_token.to_account_info(),
+                    authority: ctx.accounts.vault_authority.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                },
+                &[&seeds[..]],
+            ),
+            total,
+            ctx.accounts.mint.decimals,
+        )?;
+        ctx.accounts.vault.reload()?;
+        let vault_balance = ctx.accounts.vault.amount;
+        _solvency_guard(&mut ctx.accounts.staking, vault_balance)?;
+
+        emit!(BatchWithdrawn { user, count, amount: total_amount, reward: total_reward });
         Ok(())
     }
 
-    pub fn unpause(ctx: Context<Admin>) -> Result<()> {
-        let staking = &mut ctx.accounts.staking;
-        require!(staking.paused, StakingError::NotPaused);
-        staking.paused = false;
-        emit!(ContractUnpaused);
+    /// Mints a transferable NFT for a stake; from then on whoever holds it owns the stake.
+    /// The metadata URI is the owner-set base URI followed by the position account key.
+    /// Locked grants stay with their beneficiary and cannot be tokenized.
+    pub fn tokenize_position(ctx: Context<TokenizePosition>, stake_index: u64) -> Result<()> {
+        let position = &ctx.accounts.stake_position;
+        require_keys_eq!(position.position_mint, Pubkey::default(), StakingError::PositionTokenized);
+        require!(!position.locked, StakingError::StakeLocked);
+
+        let staking = &ctx.accounts.staking;
+        require!(!staking.position_base_uri.is_empty(), StakingError::PositionUriUnset);
+        let uri = format!("{}{}", staking.position_base_uri, position.key());
+
+        let staking_key = staking.key();
+        let seeds = &[b"vault_authority", staking_key.as_ref(), &[staking.vault_authority_bump]];
+        let signer = &[&seeds[..]];
+
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.position_mint.to_account_info(),
+                    to: ctx.accounts.position_token.to_account_info(),
+                    authority: ctx.accounts.vault_authority.to_account_info(),
+                },
+                signer,
+            ),
+            1,
+        )?;
+
+        create_metadata_accounts_v3(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_metadata_program.to_account_info(),
+                CreateMetadataAccountsV3 {
+                    metadata: ctx.accounts.metadata.to_account_info(),
+                    mint: ctx.accounts.position_mint.to_account_info(),
+                    mint_authority: ctx.accounts.vault_authority.to_account_info(),
+                    payer: ctx.accounts.user.to_account_info(),
+                    update_authority: ctx.accounts.vault_authority.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    rent: ctx.accounts.rent.to_account_info(),
+                },
+                signer,
+            ),
+            DataV2 {
+                name: format!("SNRG Stake #{}", stake_index),
+                symbol: POSITION_NFT_SYMBOL.to_string(),
+                uri,
+                seller_fee_basis_points: 0,
+                creators: None,
+                collection: None,
+                uses: None,
+            },
+            true,
+            true,
+            None,
+        )?;
+
+        // Fix the supply at one
+        token::set_authority(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                SetAuthority {
+                    current_authority: ctx.accounts.vault_authority.to_account_info(),
+                    account_or_mint: ctx.accounts.position_mint.to_account_info(),
+                },
+                signer,
+            ),
+            AuthorityType::MintTokens,
+            None,
+        )?;
+
+        let position = &mut ctx.accounts.stake_position;
+        position.position_mint = ctx.accounts.position_mint.key();
+
+        emit!(PositionTokenized {
+            user: ctx.accounts.user.key(),
+            stake_index,
+            mint: position.position_mint,
+            amount: position.amount,
+            reward: position.reward,
+            end_time: position.end_time,
+        });
         Ok(())
     }
 
-    // View functions
-    pub fn get_stake_count(ctx: Context<ViewUser>) -> Result<u64> {
-        Ok(ctx.accounts.user_stakes.stakes.len() as u64)
-    }
+    /// Rolls a matured stake into a new term, compounding its reward or paying it out.
+    /// A stake carrying a partner reward is refused (`PartnerRewardPending`): withdraw it to
+    /// collect the partner reward, then stake again. The new term books its tier's partner
+    /// reward like `stake` when the partner reward account is passed. The lock of a `stake_for`
+    /// grant ends at maturity, so the new term is unlocked.
+    pub fn restake(ctx: Context<Restake>, stake_index: u64, duration_days: u64, compound: bool) -> Result<()> {
+        require!(duration_days > 0 && duration_days <= u64::from(u32::MAX), StakingError::InvalidDuration);
+        let now = Clock::get()?.unix_timestamp;
 
-    pub fn is_solvent(ctx: Context<ViewStaking>) -> Result<bool> {
-        Ok(ctx.accounts.staking.reward_reserve >= ctx.accounts.staking.promised_rewards)
-    }
+        let old = &ctx.accounts.old_position;
+        require!(now >= old.end_time, StakingError::StakeNotMatured);
+        require_keys_eq!(old.position_mint, Pubkey::default(), StakingError::PositionTokenized);
+        require_eq!(old.partner_reward, 0, StakingError::PartnerRewardPending);
+        let (old_amount, old_reward, old_duration) = (old.amount, old.reward, old.duration_days);
 
-    pub fn get_reserve_info(ctx: Context<ViewStaking>) -> Result<(u64, u64, u64)> {
-        let s = &ctx.accounts.staking;
-        let available = s.reward_reserve.saturating_sub(s.promised_rewards);
-        Ok((s.reward_reserve, s.promised_rewards, available))
-    }
-}
+        let staking = &mut ctx.accounts.staking;
+        require!(!staking.paused, StakingError::Paused);
+        require!(!staking.emergency_mode, StakingError::EmergencyMode);
 
-// Helper for early/emergency withdrawal
-fn _withdraw_with_penalty(
-    ctx: Context<WithdrawEarlyOrEmergency>,
-    stake_index: u64,
-    fee_bps: u64,
-    is_emergency: bool,
-) -> Result<()> {
-    let user_stakes = &mut ctx.accounts.user_stakes;
-    let stake = user_stakes.stakes.get_mut(stake_index as usize).ok_or(StakingError::InvalidIndex)?;
-    require!(!stake.withdrawn, StakingError::AlreadyWithdrawn);
+        // The old reward leaves the reserve either way: into the new principal or to the user
+        staking.promised_rewards = staking.promised_rewards.checked_sub(old_reward).ok_or(StakingError::MathOverflow)?;
+        staking.reward_reserve = staking.reward_reserve.checked_sub(old_reward).ok_or(StakingError::MathOverflow)?;
 
-    if !is_emergency {
-        let now = Clock::get()?.unix_timestamp;
-        require!(now < stake.end_time, StakingError::StakeMatured);
+        let amount = if compound {
+            old_amount.checked_add(old_reward).ok_or(StakingError::MathOverflow)?
+        } else {
+            old_amount
+        };
+        let reward = _promise_term_reward(staking, amount, duration_days, now)?;
+
+        let end_time = now.checked_add((duration_days as i64) * 86_400).ok_or(StakingError::MathOverflow)?;
+
+        // The old principal's capacity is freed before the new term claims its own
+        _release_capacity(staking, &mut ctx.accounts.user_stakes, old_duration, old_amount)?;
+        _take_capacity(staking, &mut ctx.accounts.user_stakes, duration_days, amount)?;
+
+        let user_stakes = &mut ctx.accounts.user_stakes;
+        let new_stake_index = user_stakes.stake_count;
+        user_stakes.stake_count = new_stake_index.checked_add(1).ok_or(StakingError::MathOverflow)?;
+
+        let position = &mut ctx.accounts.new_position;
+        position.user = ctx.accounts.user.key();
+        position.index = new_stake_index;
+        position.amount = amount;
+        position.reward = reward;
+        position.start_time = now;
+        position.duration_days = duration_days;
+        position.end_time = end_time;
+        position.position_mint = Pubkey::default();
+        position.locked = false;
+        position.partner_mint = Pubkey::default();
+        position.partner_reward = 0;
+        position.bump = ctx.bumps.new_position;
+        let snrg_decimals = ctx.accounts.mint.decimals;
+        _attach_partner_reward(&mut ctx.accounts.partner_reward, &mut ctx.accounts.new_position, duration_days, snrg_decimals)?;
+
+        if !compound && old_reward > 0 {
+            _transfer_from_vault(&ctx.accounts, old_reward)?;
+            ctx.accounts.vault.reload()?;
+            let vault_balance = ctx.accounts.vault.amount;
+            _solvency_guard(&mut ctx.accounts.staking, vault_balance)?;
+        }
+
+        emit!(Restaked {
+            user: ctx.accounts.user.key(),
+            old_stake_index: stake_index,
+            new_stake_index,
+            amount,
+            reward,
+            end_time,
+            compounded: compound,
+        });
+
+        Ok(())
     }
 
-    stake.withdrawn = true;
+    pub fn withdraw_early(ctx: Context<Withdraw>, stake_index: u64) -> Result<()> {
+        let fee_bps = ctx.accounts.staking.early_withdrawal_fee_bps;
+        _withdraw_with_penalty(ctx, stake_index, fee_bps, false)
+    }
 
-    // Rewards forfeited → only subtract from promised, NOT from reserve
-    let staking = &mut ctx.accounts.staking;
-    staking.promised_rewards = staking.promised_rewards.checked_sub(stake.reward).ok_or(StakingError::MathOverflow)?;
+    /// Exits principal without reward; fee-free while emergency mode is on. Locked grants can
+    /// only leave early this way, and only while emergency mode is on.
+    pub fn emergency_withdraw(ctx: Context<Withdraw>, stake_index: u64) -> Result<()> {
+        let staking = &ctx.accounts.staking;
+        let fee_bps = if staking.emergency_mode { 0 } else { staking.emergency_fee_bps };
+        _withdraw_with_penalty(ctx, stake_index, fee_bps, true)
+    }
 
-    let fee = stake.amount
-        .checked_mul(fee_bps)
-        .ok_or(StakingError::MathOverflow)?
-        .checked_div(BPS_DENOMINATOR)
-        .ok_or(StakingError::MathOverflow)?;
+    // Flexible pool: no lock, rewards stream per second from the reward reserve
+    pub fn flex_deposit(ctx: Context<FlexDeposit>, amount: u64) -> Result<()> {
+        require!(amount > 0, StakingError::ZeroAmount);
+        let staking = &mut ctx.accounts.staking;
+        require!(staking.is_funded, StakingError::NotFunded);
+        require!(!staking.paused, StakingError::Paused);
+        require!(!staking.emergency_mode, StakingError::EmergencyMode);
 
-    require!(fee < stake.amount, StakingError::FeeExceedsAmount);
-    let return_amount = stake.amount - fee;
+        let now = Clock::get()?.unix_timestamp;
+        _update_flex_pool(staking, now)?;
+        let position = &mut ctx.accounts.flex_position;
+        position.user = ctx.accounts.user.key();
+        position.bump = ctx.bumps.flex_position;
+        _settle_flex_position(staking, position)?;
+        let was_open = _flex_position_open(position);
 
-    // Transfer fee to treasury
-    if fee > 0 {
-        _transfer_from_vault(&ctx.accounts, fee)?;
+        // Transfer principal to vault
+        let before = ctx.accounts.user_token.amount;
         token::transfer_checked(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
                 TransferChecked {
-                    from: ctx.accounts.vault.to_account_info(),
-                    to: ctx.accounts.treasury_token.to_account_info(),
-                    authority: ctx.accounts.vault_authority.to_account_info(),
+                    from: ctx.accounts.user_token.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
                     mint: ctx.accounts.mint.to_account_info(),
                 },
             ),
-            fee,
+            amount,
             ctx.accounts.mint.decimals,
         )?;
-    }
+        let actual_received = before - ctx.accounts.user_token.reload()?.amount;
 
-    // Transfer principal back
-    if return_amount > 0 {
-        _transfer_from_vault_to_user(&ctx.accounts, return_amount)?;
-    }
+        let position = &mut ctx.accounts.flex_position;
+        position.amount = position.amount.checked_add(actual_received).ok_or(StakingError::MathOverflow)?;
+        let staking = &mut ctx.accounts.staking;
+        _check_tvl_cap(staking, actual_received)?;
+        staking.flex_total_staked = staking.flex_total_staked.checked_add(actual_received).ok_or(StakingError::MathOverflow)?;
+        _track_flex_position(staking, was_open, &ctx.accounts.flex_position)?;
 
-    if is_emergency {
-        emit!(EmergencyWithdrawal {
+        emit!(FlexDeposited {
             user: ctx.accounts.user.key(),
-            stake_index,
-            amount: return_amount,
-            fee,
+            amount: actual_received,
+            total_staked: staking.flex_total_staked,
         });
-    } else {
-        emit!(WithdrawnEarly {
+        Ok(())
+    }
+
+    pub fn flex_withdraw(ctx: Context<FlexWithdraw>, amount: u64) -> Result<()> {
+        require!(amount > 0, StakingError::ZeroAmount);
+        let now = Clock::get()?.unix_timestamp;
+        let staking = &mut ctx.accounts.staking;
+        _update_flex_pool(staking, now)?;
+        let position = &mut ctx.accounts.flex_position;
+        _settle_flex_position(staking, position)?;
+        require!(position.amount >= amount, StakingError::InsufficientFlexBalance);
+        let was_open = _flex_position_open(position);
+
+        position.amount -= amount;
+        staking.flex_total_staked = staking.flex_total_staked.checked_sub(amount).ok_or(StakingError::MathOverflow)?;
+        _track_flex_position(staking, was_open, position)?;
+
+        _transfer_from_vault(&ctx.accounts, amount)?;
+        ctx.accounts.vault.reload()?;
+        let vault_balance = ctx.accounts.vault.amount;
+        _solvency_guard(&mut ctx.accounts.staking, vault_balance)?;
+
+        emit!(FlexWithdrawn {
             user: ctx.accounts.user.key(),
-            stake_index,
-            amount: return_amount,
-            fee,
+            amount,
+            total_staked: ctx.accounts.staking.flex_total_staked,
         });
+        Ok(())
     }
 
-    Ok(())
-}
+    pub fn flex_claim(ctx: Context<FlexWithdraw>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let staking = &mut ctx.accounts.staking;
+        _update_flex_pool(staking, now)?;
+        let position = &mut ctx.accounts.flex_position;
+        _settle_flex_position(staking, position)?;
 
-// CPI helpers
-fn _transfer_to_vault(accounts: &impl GetAccounts, amount: u64) -> Result<()> {
-    token::transfer_checked(
-        CpiContext::new(
-            accounts.token_program().to_account_info(),
-            TransferChecked {
-                from: accounts.treasury_token().to_account_info(),
-                to: accounts.vault().to_account_info(),
-                authority: accounts.treasury_authority().to_account_info(),
-                mint: accounts.mint().to_account_info(),
-            },
-        ),
-        amount,
-        accounts.mint().decimals,
-    )
-}
+        let amount = position.pending_rewards;
+        require!(amount > 0, StakingError::NothingToClaim);
+        position.pending_rewards = 0;
 
-fn _transfer_to_vault_user_to_program(accounts: &Stake, amount: u64) -> Result<()> {
-    token::transfer_checked(
-        CpiContext::new(
-            accounts.token_program.to_account_info(),
-            TransferChecked {
-                from: accounts.user_token.to_account_info(),
-                to: accounts.vault.to_account_info(),
-                authority: accounts.user.to_account_info(),
-                mint: accounts.mint.to_account_info(),
-            },
-        ),
-        amount,
-        accounts.mint.decimals,
-    )
-}
+        // Accrued rewards were already promised out of the reserve
+        staking.promised_rewards = staking.promised_rewards.checked_sub(amount).ok_or(StakingError::MathOverflow)?;
+        staking.reward_reserve = staking.reward_reserve.checked_sub(amount).ok_or(StakingError::MathOverflow)?;
+        staking.flex_promised = staking.flex_promised.checked_sub(amount).ok_or(StakingError::MathOverflow)?;
+        _track_flex_position(staking, true, position)?;
 
-fn _transfer_from_vault(accounts: &impl GetAccounts, amount: u64) -> Result<()> {
-    let seeds = &[b"staking", accounts.staking().treasury.as_ref(), &[accounts.staking().bump]];
-    token::transfer_checked(
-        CpiContext::new_with_signer(
-            accounts.token_program().to_account_info(),
-            TransferChecked {
-                from: accounts.vault().to_account_info(),
-                to: accounts.user_token().to_account_info(),
-                authority: accounts.vault_authority().to_account_info(),
-                mint: accounts.mint().to_account_info(),
-            },
-            &[&seeds[..]],
-        ),
-        amount,
-        accounts.mint().decimals,
-    )
-}
+        _transfer_from_vault(&ctx.accounts, amount)?;
+        ctx.accounts.vault.reload()?;
+        let vault_balance = ctx.accounts.vault.amount;
+        _solvency_guard(&mut ctx.accounts.staking, vault_balance)?;
 
-fn _transfer_from_vault_to_user(accounts: &impl GetAccounts, amount: u64) -> Result<()> {
-    _transfer_from_vault(accounts, amount)
-}
+        emit!(FlexRewardsClaimed { user: ctx.accounts.user.key(), amount });
+        Ok(())
+    }
 
-// Accounts
-#[account]
-pub struct Staking {
-    pub treasury: Pubkey,
-    pub snrg_mint: Pubkey,
-    pub vault_authority: Pubkey,
-    pub is_funded: bool,
-    pub paused: bool,
-    pub reward_reserve: u64,
-    pub promised_rewards: u64,
-    pub reward_rates: std::collections::BTreeMap<u64, u64>, // days → bps
-    pub bump: u8,
-}
+    // Admin
+    /// Owner sets the flexible pool emission in SNRG base units per second
+    pub fn set_flex_reward_rate(ctx: Context<Admin>, rate_per_second: u64) -> Result<()> {
+        let staking = &mut ctx.accounts.staking;
+        _update_flex_pool(staking, Clock::get()?.unix_timestamp)?;
+        staking.flex_reward_rate = rate_per_second;
+        emit!(FlexRewardRateSet { rate_per_second });
+        Ok(())
+    }
 
-#[account]
-pub struct UserStakes {
-    pub user: Pubkey,
-    pub stakes: Vec<Stake>,
-}
+    /// Owner sets how long reward rate changes wait before they can be applied (0 = immediate).
+    /// Raising the delay takes effect at once; lowering it is queued behind the current delay.
+    pub fn set_rate_change_delay(ctx: Context<Admin>, delay: i64) -> Result<()> {
+        require!((0..=MAX_RATE_CHANGE_DELAY).contains(&delay), StakingError::InvalidDelay);
+        let staking = &mut ctx.accounts.staking;
+        // Any queued decrease is superseded
+        staking.pending_rate_change_delay = 0;
+        staking.rate_change_delay_eta = 0;
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
-pub struct Stake {
-    pub amount: u64,
-    pub reward: u64,
-    pub end_time: i64,
-    pub withdrawn: bool,
-}
+        if delay >= staking.rate_change_delay {
+            staking.rate_change_delay = delay;
+            emit!(RateChangeDelaySet { delay });
+        } else {
+            let eta = Clock::get()?.unix_timestamp.checked_add(staking.rate_change_delay).ok_or(StakingError::MathOverflow)?;
+            staking.pending_rate_change_delay = delay;
+            staking.rate_change_delay_eta = eta;
+            emit!(RateChangeDelayQueued { delay, eta });
+        }
+        Ok(())
+    }
+
+    /// Owner applies a queued delay decrease once the current delay has passed
+    pub fn apply_rate_change_delay(ctx: Context<Admin>) -> Result<()> {
+        let staking = &mut ctx.accounts.staking;
+        require!(staking.rate_change_delay_eta != 0, StakingError::NoPendingRateChange);
+        require!(Clock::get()?.unix_timestamp >= staking.rate_change_delay_eta, StakingError::RateChangeNotReady);
+
+        let delay = staking.pending_rate_change_delay;
+        staking.rate_change_delay = delay;
+        staking.pending_rate_change_delay = 0;
+        staking.rate_change_delay_eta = 0;
+        emit!(RateChangeDelaySet { delay });
+        Ok(())
+    }
+
+    /// Owner adds a duration or changes its rate; existing stakes keep the reward they locked in
+    pub fn set_reward_rate(ctx: Context<Admin>, duration_days: u64, reward_bps: u64) -> Result<()> {
+        require!(duration_days > 0 && duration_days <= u64::from(u32::MAX), StakingError::InvalidDuration);
+        require!(reward_bps > 0 && reward_bps <= MAX_REWARD_RATE_BPS, StakingError::RateTooHigh);
+        _queue_rate_change(&mut ctx.accounts.staking, duration_days, Some(reward_bps))
+    }
+
+    /// Owner retires a duration for new stakes; existing stakes are unaffected
+    pub fn remove_reward_rate(ctx: Context<Admin>, duration_days: u64) -> Result<()> {
+        require!(ctx.accounts.staking.reward_rates.contains_key(&duration_days), StakingError::InvalidDuration);
+        _queue_rate_change(&mut ctx.accounts.staking, duration_days, None)
+    }
+
+    /// Owner applies a queued rate change once its delay has passed
+    pub fn apply_rate_change(ctx: Context<Admin>, duration_days: u64) -> Result<()> {
+        let staking = &mut ctx.accounts.staking;
+        let index = staking
+            .pending_rate_changes
+            .iter()
+            .position(|c| c.duration_days == duration_days)
+            .ok_or(StakingError::NoPendingRateChange)?;
+        require!(Clock::get()?.unix_timestamp >= staking.pending_rate_changes[index].eta, StakingError::RateChangeNotReady);
+
+        let change = staking.pending_rate_changes.remove(index);
+        _apply_rate_change(staking, change.duration_days, change.reward_bps)
+    }
+
+    pub fn cancel_rate_change(ctx: Context<Admin>, duration_days: u64) -> Result<()> {
+        let staking = &mut ctx.accounts.staking;
+        let index = staking
+            .pending_rate_changes
+            .iter()
+            .position(|c| c.duration_days == duration_days)
+            .ok_or(StakingError::NoPendingRateChange)?;
+        staking.pending_rate_changes.remove(index);
+        emit!(RateChangeCanceled { duration_days });
+        Ok(())
+    }
+
+    /// Owner sets the early and emergency withdrawal fees, each bounded by its maximum
+    pub fn set_withdrawal_fees(ctx: Context<Admin>, early_bps: u64, emergency_bps: u64) -> Result<()> {
+        require!(early_bps <= MAX_EARLY_WITHDRAWAL_FEE_BPS, StakingError::FeeTooHigh);
+        require!(emergency_bps <= MAX_EMERGENCY_FEE_BPS, StakingError::FeeTooHigh);
+
+        let staking = &mut ctx.accounts.staking;
+        staking.early_withdrawal_fee_bps = early_bps;
+        staking.emergency_fee_bps = emergency_bps;
+        emit!(WithdrawalFeesSet { early_bps, emergency_bps });
+        Ok(())
+    }
+
+    /// Owner sets how penalties are split between burning, the treasury and the reward reserve
+    pub fn set_fee_split(ctx: Context<Admin>, burn_bps: u64, treasury_bps: u64, reserve_bps: u64) -> Result<()> {
+        let total = burn_bps
+            .checked_add(treasury_bps)
+            .and_then(|t| t.checked_add(reserve_bps))
+            .ok_or(StakingError::MathOverflow)?;
+        require_eq!(total, BPS_DENOMINATOR, StakingError::InvalidFeeSplit);
+
+        let staking = &mut ctx.accounts.staking;
+        staking.fee_burn_bps = burn_bps;
+        staking.fee_treasury_bps = treasury_bps;
+        staking.fee_reserve_bps = reserve_bps;
+        emit!(FeeSplitSet { burn_bps, treasury_bps, reserve_bps });
+        Ok(())
+    }
+
+    /// Owner opts a duration tier into pro-rata rewards on early withdrawal: the reward earned so far,
+    /// scaled by `payout_bps`, is paid out. `None` restores full forfeiture for the tier.
+    pub fn set_early_reward_payout(ctx: Context<Admin>, duration_days: u64, payout_bps: Option<u64>) -> Result<()> {
+        let staking = &mut ctx.accounts.staking;
+        match payout_bps {
+            Some(bps) => {
+                require!(bps > 0 && bps <= BPS_DENOMINATOR, StakingError::InvalidPayout);
+                require!(
+                    staking.early_reward_payout.contains_key(&duration_days)
+                        || staking.early_reward_payout.len() < MAX_REWARD_RATES,
+                    StakingError::TooManyRewardRates
+                );
+                staking.early_reward_payout.insert(duration_days, bps);
+            }
+            None => {
+                staking.early_reward_payout.remove(&duration_days);
+            }
+        }
+        emit!(EarlyRewardPayoutSet { duration_days, payout_bps });
+        Ok(())
+    }
+
+    /// Owner caps total principal across term stakes and the flexible pool (0 = unlimited)
+    pub fn set_tvl_cap(ctx: Context<Admin>, cap: u64) -> Result<()> {
+        ctx.accounts.staking.tvl_cap = cap;
+        emit!(TvlCapSet { cap });
+        Ok(())
+    }
+
+    /// Owner caps term principal in one duration tier (`None` = unlimited)
+    pub fn set_tier_cap(ctx: Context<Admin>, duration_days: u64, cap: Option<u64>) -> Result<()> {
+        let staking = &mut ctx.accounts.staking;
+        match cap {
+            Some(cap) => {
+                require!(
+                    staking.tier_caps.contains_key(&duration_days) || staking.tier_caps.len() < MAX_REWARD_RATES,
+                    StakingError::TooManyRewardRates
+                );
+                staking.tier_caps.insert(duration_days, cap);
+            }
+            None => {
+                staking.tier_caps.remove(&duration_days);
+            }
+        }
+        emit!(TierCapSet { duration_days, cap });
+        Ok(())
+    }
+
+    /// Owner sets the metadata base URI for position NFTs; each NFT points at `{base_uri}{position}`
+    pub fn set_position_base_uri(ctx: Context<Admin>, base_uri: String) -> Result<()> {
+        require!(base_uri.len() <= MAX_POSITION_BASE_URI_LEN, StakingError::PositionUriTooLong);
+        ctx.accounts.staking.position_base_uri = base_uri.clone();
+        emit!(PositionBaseUriSet { base_uri });
+        Ok(())
+    }
+
+    /// Owner caps each user's open term principal (0 = unlimited)
+    pub fn set_max_user_principal(ctx: Context<Admin>, max: u64) -> Result<()> {
+        ctx.accounts.staking.max_user_principal = max;
+        emit!(MaxUserPrincipalSet { max });
+        Ok(())
+    }
+
+    /// Owner halts new stakes and lets every staker, locked grants included, exit principal
+    /// fee-free via `emergency_withdraw`
+    pub fn enable_emergency_mode(ctx: Context<Admin>) -> Result<()> {
+        let staking = &mut ctx.accounts.staking;
+        require!(!staking.emergency_mode, StakingError::EmergencyMode);
+        staking.emergency_mode = true;
+        emit!(EmergencyModeSet { enabled: true });
+        Ok(())
+    }
+
+    pub fn disable_emergency_mode(ctx: Context<Admin>) -> Result<()> {
+        let staking = &mut ctx.accounts.staking;
+        require!(staking.emergency_mode, StakingError::NotEmergencyMode);
+        staking.emergency_mode = false;
+        emit!(EmergencyModeSet { enabled: false });
+        Ok(())
+    }
+
+    /// Anyone: pauses the contract if reserves no longer cover promised rewards or the vault no
+    /// longer holds principal plus promised rewards. A stake that would cause a shortfall is
+    /// rejected outright, and every vault outflow runs the same guard, but a shortfall that
+    /// arises without a transfer (e.g. flex accrual outrunning the reserve) only pauses here,
+    /// so keepers calling this are the pause trigger.
+    pub fn check_solvency(ctx: Context<CheckSolvency>) -> Result<bool> {
+        let vault_balance = ctx.accounts.vault.amount;
+        let staking = &mut ctx.accounts.staking;
+        _update_flex_pool(staking, Clock::get()?.unix_timestamp)?;
+        let insolvent = _solvency_guard(staking, vault_balance)?;
+        Ok(!insolvent)
+    }
+
+    /// Owner registers a partner reward mint; its reserve lives in a dedicated vault
+    pub fn add_partner_reward(ctx: Context<AddPartnerReward>) -> Result<()> {
+        let staking = &mut ctx.accounts.staking;
+        require!(staking.partner_mints.len() < MAX_PARTNER_MINTS, StakingError::TooManyPartnerMints);
+        let reward_mint = ctx.accounts.reward_mint.key();
+        require_keys_neq!(reward_mint, staking.snrg_mint, StakingError::InvalidPartnerReward);
+        staking.partner_mints.push(reward_mint);
+
+        let partner = &mut ctx.accounts.partner_reward;
+        partner.staking = staking.key();
+        partner.reward_mint = reward_mint;
+        partner.vault = ctx.accounts.partner_vault.key();
+        partner.reward_reserve = 0;
+        partner.promised_rewards = 0;
+        partner.rates = std::collections::BTreeMap::new();
+        partner.bump = ctx.bumps.partner_reward;
+
+        emit!(PartnerRewardAdded { mint: reward_mint });
+        Ok(())
+    }
+
+    /// Anyone (typically the partner) funds a partner reward reserve
+    pub fn fund_partner_reward(ctx: Context<FundPartnerReward>, amount: u64) -> Result<()> {
+        require!(amount > 0, StakingError::ZeroAmount);
+        let before = ctx.accounts.partner_vault.amount;
+        token::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.funder_token.to_account_info(),
+                    to: ctx.accounts.partner_vault.to_account_info(),
+                    authority: ctx.accounts.funder.to_account_info(),
+                    mint: ctx.accounts.reward_mint.to_account_info(),
+                },
+            ),
+            amount,
+            ctx.accounts.reward_mint.decimals,
+        )?;
+        let received = ctx.accounts.partner_vault.reload()?.amount - before; // book what actually arrived
+
+        let partner = &mut ctx.accounts.partner_reward;
+        partner.reward_reserve = partner.reward_reserve.checked_add(received).ok_or(StakingError::MathOverflow)?;
+        emit!(PartnerRewardFunded { mint: partner.reward_mint, amount: received });
+        Ok(())
+    }
+
+    /// Owner takes back partner reserve that is not promised to any stake
+    pub fn withdraw_partner_excess(ctx: Context<PartnerTreasury>, amount: u64) -> Result<()> {
+        require!(amount > 0, StakingError::ZeroAmount);
+        let partner = &mut ctx.accounts.partner_reward;
+        let available = partner.reward_reserve.saturating_sub(partner.promised_rewards);
+        require!(amount <= available, StakingError::PartnerReserveShortfall);
+        partner.reward_reserve -= amount;
+        let (mint, remaining_reserve) = (partner.reward_mint, partner.reward_reserve);
+
+        _transfer_partner_to_treasury(&ctx.accounts, amount)?;
+
+        emit!(PartnerExcessWithdrawn { mint, amount, remaining_reserve });
+        Ok(())
+    }
+
+    /// Owner retires a partner mint once none of its reward is promised: the vault balance goes
+    /// to the treasury, the partner accounts are closed and the mint's tiers and slot are freed
+    pub fn remove_partner_reward(ctx: Context<PartnerTreasury>) -> Result<()> {
+        let partner = &ctx.accounts.partner_reward;
+        require_eq!(partner.promised_rewards, 0, StakingError::PartnerRewardPending);
+        let mint = partner.reward_mint;
+
+        let swept = ctx.accounts.partner_vault.amount;
+        if swept > 0 {
+            _transfer_partner_to_treasury(&ctx.accounts, swept)?;
+        }
+
+        let staking_key = ctx.accounts.staking.key();
+        let seeds = &[b"vault_authority", staking_key.as_ref(), &[ctx.accounts.staking.vault_authority_bump]];
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.partner_vault.to_account_info(),
+                destination: ctx.accounts.treasury.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            },
+            &[&seeds[..]],
+        ))?;
+        ctx.accounts.partner_reward.close(ctx.accounts.treasury.to_account_info())?;
+
+        let staking = &mut ctx.accounts.staking;
+        staking.partner_mints.retain(|m| *m != mint);
+        staking.partner_tiers.retain(|_, m| *m != mint);
+
+        emit!(PartnerRewardRemoved { mint, swept });
+        Ok(())
+    }
+
+    /// Owner sets a tier's partner reward, in partner base units per whole SNRG staked (`None`
+    /// removes it). Each tier carries at most one partner, so a position earns at most one.
+    pub fn set_partner_rate(ctx: Context<AdminPartner>, duration_days: u64, units_per_snrg: Option<u64>) -> Result<()> {
+        let staking = &mut ctx.accounts.staking;
+        let partner = &mut ctx.accounts.partner_reward;
+        let reward_mint = partner.reward_mint;
+        match units_per_snrg {
+            Some(rate) => {
+                require!(rate > 0, StakingError::InvalidPartnerReward);
+                match staking.partner_tiers.get(&duration_days) {
+                    Some(mint) => require_keys_eq!(*mint, reward_mint, StakingError::TierHasPartner),
+                    None => require!(staking.partner_tiers.len() < MAX_REWARD_RATES, StakingError::TooManyRewardRates),
+                }
+                require!(
+                    partner.rates.contains_key(&duration_days) || partner.rates.len() < MAX_REWARD_RATES,
+                    StakingError::TooManyRewardRates
+                );
+                partner.rates.insert(duration_days, rate);
+                staking.partner_tiers.insert(duration_days, reward_mint);
+            }
+            None => {
+                if partner.rates.remove(&duration_days).is_some() {
+                    staking.partner_tiers.remove(&duration_days);
+                }
+            }
+        }
+        emit!(PartnerRateSet { mint: reward_mint, duration_days, units_per_snrg });
+        Ok(())
+    }
+
+    pub fn pause(ctx: Context<Admin>) -> Result<()> {
+        let staking = &mut ctx.accounts.staking;
+        require!(!staking.paused, StakingError::AlreadyPaused);
+        staking.paused = true;
+        emit!(ContractPaused);
+        Ok(())
+    }
+
+    pub fn unpause(ctx: Context<Admin>) -> Result<()> {
+        let staking = &mut ctx.accounts.staking;
+        require!(staking.paused, StakingError::NotPaused);
+        staking.paused = false;
+        emit!(ContractUnpaused);
+        Ok(())
+    }
+
+    /// Owner takes back reserve that is not promised to any stake; never dips below promised rewards
+    pub fn withdraw_excess_reserve(ctx: Context<TreasuryWithdraw>, amount: u64) -> Result<()> {
+        require!(amount > 0, StakingError::ZeroAmount);
+        let staking = &mut ctx.accounts.staking;
+        // Accrued flexible pool rewards count as promised
+        _update_flex_pool(staking, Clock::get()?.unix_timestamp)?;
+
+        let available = staking.reward_reserve.saturating_sub(staking.promised_rewards);
+        require!(amount <= available, StakingError::InsufficientReserves);
+        staking.reward_reserve -= amount;
+
+        _transfer_vault_to_treasury(&ctx.accounts, amount)?;
+        ctx.accounts.vault.reload()?;
+        let vault_balance = ctx.accounts.vault.amount;
+        _solvency_guard(&mut ctx.accounts.staking, vault_balance)?;
+
+        emit!(ExcessReserveWithdrawn { amount, remaining_reserve: ctx.accounts.staking.reward_reserve });
+        Ok(())
+    }
+
+    /// Owner winds the program down once every stake is settled and every partner reward has
+    /// been removed: the vault balance goes to the treasury and the vault and staking accounts
+    /// are closed
+    pub fn close_staking(ctx: Context<CloseStaking>) -> Result<()> {
+        let staking = &ctx.accounts.staking;
+        require!(
+            staking.active_stakes == 0 && staking.flex_total_staked == 0 && staking.promised_rewards == 0,
+            StakingError::StakesOutstanding
+        );
+        // Each removal checks the partner's promised rewards and sweeps and closes its vault
+        require!(staking.partner_mints.is_empty(), StakingError::PartnerRewardsOutstanding);
+
+        let swept = ctx.accounts.vault.amount;
+        if swept > 0 {
+            _transfer_vault_to_treasury(&ctx.accounts, swept)?;
+        }
+
+        let staking_key = staking.key();
+        let seeds = &[b"vault_authority", staking_key.as_ref(), &[staking.vault_authority_bump]];
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.vault.to_account_info(),
+                destination: ctx.accounts.treasury.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            },
+            &[&seeds[..]],
+        ))?;
+
+        emit!(StakingClosed { swept });
+        Ok(())
+    }
+
+    // View functions
+    /// Stakes ever opened by the user; indices run 0..count and are never reused
+    pub fn get_stake_count(ctx: Context<ViewUser>) -> Result<u64> {
+        Ok(ctx.accounts.user_stakes.stake_count)
+    }
+
+    pub fn is_solvent(ctx: Context<ViewStaking>) -> Result<bool> {
+        Ok(ctx.accounts.staking.reward_reserve >= ctx.accounts.staking.promised_rewards)
+    }
+
+    /// Reserve info for SNRG first, then for each `PartnerReward` passed as a remaining account
+    pub fn get_reserve_info<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ViewStaking<'info>>,
+    ) -> Result<Vec<ReserveInfo>> {
+        let s = &ctx.accounts.staking;
+        let mut info = vec![ReserveInfo {
+            mint: s.snrg_mint,
+            reserve: s.reward_reserve,
+            promised: s.promised_rewards,
+            available: s.reward_reserve.saturating_sub(s.promised_rewards),
+        }];
+        for account in ctx.remaining_accounts {
+            let partner = Account::<PartnerReward>::try_from(account)?;
+            require_keys_eq!(partner.staking, s.key(), StakingError::InvalidPartnerReward);
+            info.push(ReserveInfo {
+                mint: partner.reward_mint,
+                reserve: partner.reward_reserve,
+                promised: partner.promised_rewards,
+                available: partner.reward_reserve.saturating_sub(partner.promised_rewards),
+            });
+        }
+        Ok(info)
+    }
+
+    /// Flexible pool rewards the position could claim right now
+    pub fn get_flex_pending_rewards(ctx: Context<ViewFlex>) -> Result<u64> {
+        let (reward_per_token, _) = _flex_accrual(&ctx.accounts.staking, Clock::get()?.unix_timestamp)?;
+        _flex_pending(&ctx.accounts.flex_position, reward_per_token)
+    }
+
+    /// Remaining (global, tier) capacity for a new stake of `duration_days`; u64::MAX = unlimited
+    pub fn get_remaining_capacity(ctx: Context<ViewStaking>, duration_days: u64) -> Result<(u64, u64)> {
+        let s = &ctx.accounts.staking;
+        let global = match s.tvl_cap {
+            0 => u64::MAX,
+            cap => cap.saturating_sub(s.total_term_staked.saturating_add(s.flex_total_staked)),
+        };
+        let tier = match s.tier_caps.get(&duration_days) {
+            Some(cap) => cap.saturating_sub(s.tier_staked.get(&duration_days).copied().unwrap_or(0)),
+            None => u64::MAX,
+        };
+        Ok((global, tier))
+    }
+
+    /// Principal the user can still stake before hitting the per-user maximum; u64::MAX = unlimited
+    pub fn get_user_remaining_capacity(ctx: Context<ViewUserCapacity>) -> Result<u64> {
+        Ok(match ctx.accounts.staking.max_user_principal {
+            0 => u64::MAX,
+            max => max.saturating_sub(ctx.accounts.user_stakes.active_principal),
+        })
+    }
+
+    /// (amount, reward, end_time) of a stake position, e.g. for NFT metadata
+    pub fn get_position_info(ctx: Context<ViewPosition>) -> Result<(u64, u64, i64)> {
+        let p = &ctx.accounts.stake_position;
+        Ok((p.amount, p.reward, p.end_time))
+    }
+
+    /// Active rate table as (duration in days, bps) pairs, shortest duration first
+    pub fn get_reward_rates(ctx: Context<ViewStaking>) -> Result<Vec<(u64, u64)>> {
+        Ok(ctx.accounts.staking.reward_rates.iter().map(|(days, bps)| (*days, *bps)).collect())
+    }
+}
+
+// Shared by `stake` and `stake_with_bonus`; a bonus is moved in from `bonus_source` and booked as reward
+fn _stake_tokens(ctx: Context<StakeTokens>, amount: u64, duration_days: u64, bonus: u64) -> Result<()> {
+    require!(amount > 0, StakingError::ZeroAmount);
+    require!(duration_days > 0 && duration_days <= u64::from(u32::MAX), StakingError::InvalidDuration);
+
+    let staking = &ctx.accounts.staking;
+    require!(staking.is_funded, StakingError::NotFunded);
+    require!(!staking.paused, StakingError::Paused);
+    require!(!staking.emergency_mode, StakingError::EmergencyMode);
+
+    // Transfer principal to vault
+    let before = ctx.accounts.user_token.amount;
+    _transfer_to_vault_user_to_program(&ctx.accounts, amount)?;
+    let actual_received = ctx.accounts.user_token.reload()?.amount;
+    let actual_received = before - actual_received; // delta check
+
+    let (stake_index, reward, end_time) = _open_stake(
+        &mut ctx.accounts.staking,
+        &mut ctx.accounts.user_stakes,
+        &mut ctx.accounts.stake_position,
+        ctx.accounts.user.key(),
+        actual_received,
+        duration_days,
+        false,
+    )?;
+    ctx.accounts.user_stakes.bump = ctx.bumps.user_stakes;
+    ctx.accounts.stake_position.bump = ctx.bumps.stake_position;
+    let snrg_decimals = ctx.accounts.mint.decimals;
+    _attach_partner_reward(&mut ctx.accounts.partner_reward, &mut ctx.accounts.stake_position, duration_days, snrg_decimals)?;
+
+    // The bonus joins the reserve and the position's reward, so exits before maturity forfeit it
+    let reward = if bonus > 0 {
+        let funder = ctx.accounts.bonus_funder.as_ref().ok_or(StakingError::MissingBonusAccounts)?;
+        let source = ctx.accounts.bonus_source.as_mut().ok_or(StakingError::MissingBonusAccounts)?;
+        let before = source.amount;
+        token::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: source.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: funder.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                },
+            ),
+            bonus,
+            ctx.accounts.mint.decimals,
+        )?;
+        let received = before - source.reload()?.amount;
+
+        let staking = &mut ctx.accounts.staking;
+        staking.reward_reserve = staking.reward_reserve.checked_add(received).ok_or(StakingError::MathOverflow)?;
+        staking.promised_rewards = staking.promised_rewards.checked_add(received).ok_or(StakingError::MathOverflow)?;
+        let position = &mut ctx.accounts.stake_position;
+        position.reward = position.reward.checked_add(received).ok_or(StakingError::MathOverflow)?;
+
+        emit!(StakeBonusAdded { user: position.user, stake_index, bonus: received, funder: funder.key() });
+        position.reward
+    } else {
+        reward
+    };
+
+    emit!(Staked {
+        user: ctx.accounts.user.key(),
+        stake_index,
+        amount: actual_received,
+        reward,
+        end_time,
+    });
+
+    Ok(())
+}
+
+// Helper for early/emergency withdrawal
+fn _withdraw_with_penalty(
+    ctx: Context<Withdraw>,
+    stake_index: u64,
+    fee_bps: u64,
+    is_emergency: bool,
+) -> Result<()> {
+    let stake = &ctx.accounts.stake_position;
+    let now = Clock::get()?.unix_timestamp;
+    if !is_emergency {
+        require!(now < stake.end_time, StakingError::StakeMatured);
+    }
+    let emergency_exit = is_emergency && ctx.accounts.staking.emergency_mode;
+    require!(!stake.locked || now >= stake.end_time || emergency_exit, StakingError::StakeLocked);
+    // Partner rewards are forfeited on any exit before maturity
+    let matured = now >= stake.end_time;
+    _settle_partner_reward(ctx.accounts, stake_index, matured)?;
+
+    let stake = &ctx.accounts.stake_position;
+    _redeem_position(
+        stake,
+        &ctx.accounts.user,
+        &ctx.accounts.position_mint,
+        &ctx.accounts.position_token,
+        &ctx.accounts.token_program,
+    )?;
+
+    // Early withdrawals on tiers with a payout policy keep part of the reward earned so far
+    let staking = &mut ctx.accounts.staking;
+    let reward = if is_emergency {
+        0
+    } else {
+        _early_reward(staking, stake, now)?
+    };
+
+    // Forfeited rewards → only subtract from promised, NOT from reserve; paid rewards leave both
+    staking.promised_rewards = staking.promised_rewards.checked_sub(stake.reward).ok_or(StakingError::MathOverflow)?;
+    staking.reward_reserve = staking.reward_reserve.checked_sub(reward).ok_or(StakingError::MathOverflow)?;
+
+    let fee = stake.amount
+        .checked_mul(fee_bps)
+        .ok_or(StakingError::MathOverflow)?
+        .checked_div(BPS_DENOMINATOR)
+        .ok_or(StakingError::MathOverflow)?;
+
+    require!(fee < stake.amount, StakingError::FeeExceedsAmount);
+    let return_amount = stake.amount - fee;
+    _close_stake_slot(&mut ctx.accounts.staking, &mut ctx.accounts.user_stakes, stake.duration_days, stake.amount)?;
+
+    // Split the fee: burn, treasury, and the remainder stays in the vault as reward reserve
+    if fee > 0 {
+        let staking = &ctx.accounts.staking;
+        let burned = _bps_of(fee, staking.fee_burn_bps)?;
+        let to_treasury = _bps_of(fee, staking.fee_treasury_bps)?;
+        let to_reserve = fee - burned - to_treasury;
+
+        let staking_key = staking.key();
+        let seeds = &[b"vault_authority", staking_key.as_ref(), &[staking.vault_authority_bump]];
+        if burned > 0 {
+            token::burn(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Burn {
+                        mint: ctx.accounts.mint.to_account_info(),
+                        from: ctx.accounts.vault.to_account_info(),
+                        authority: ctx.accounts.vault_authority.to_account_info(),
+                    },
+                    &[&seeds[..]],
+                ),
+                burned,
+            )?;
+        }
+        if to_treasury > 0 {
+            token::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.vault.to_account_info(),
+                        to: ctx.accounts.treasury_token.to_account_info(),
+                        authority: ctx.accounts.vault_authority.to_account_info(),
+                        mint: ctx.accounts.mint.to_account_info(),
+                    },
+                    &[&seeds[..]],
+                ),
+                to_treasury,
+                ctx.accounts.mint.decimals,
+            )?;
+        }
+
+        let staking = &mut ctx.accounts.staking;
+        staking.reward_reserve = staking.reward_reserve.checked_add(to_reserve).ok_or(StakingError::MathOverflow)?;
+        emit!(PenaltyDistributed { burned, to_treasury, to_reserve });
+    }
+
+    // Transfer principal (and any pro-rata reward) back
+    let payout = return_amount.checked_add(reward).ok_or(StakingError::MathOverflow)?;
+    if payout > 0 {
+        _transfer_from_vault_to_user(&ctx.accounts, payout)?;
+    }
+    ctx.accounts.vault.reload()?;
+    let vault_balance = ctx.accounts.vault.amount;
+    _solvency_guard(&mut ctx.accounts.staking, vault_balance)?;
+
+    if is_emergency {
+        emit!(EmergencyWithdrawal {
+            user: ctx.accounts.user.key(),
+            stake_index,
+            amount: return_amount,
+            fee,
+        });
+    } else {
+        emit!(WithdrawnEarly {
+            user: ctx.accounts.user.key(),
+            stake_index,
+            amount: return_amount,
+            reward,
+            fee,
+        });
+    }
+
+    Ok(())
+}
+
+// Checks the signer owns the position: the original staker, or the NFT holder once tokenized.
+// The NFT is burned as the position is redeemed.
+fn _redeem_position<'info>(
+    position: &StakePosition,
+    user: &Signer<'info>,
+    position_mint: &Option<Account<'info, Mint>>,
+    position_token: &Option<Account<'info, TokenAccount>>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    if position.position_mint == Pubkey::default() {
+        require_keys_eq!(user.key(), position.user, StakingError::NotPositionOwner);
+        return Ok(());
+    }
+
+    let (position_mint, position_token) = match (position_mint, position_token) {
+        (Some(mint), Some(token)) => (mint, token),
+        _ => return err!(StakingError::NotPositionOwner),
+    };
+    require_keys_eq!(position_mint.key(), position.position_mint, StakingError::NotPositionOwner);
+    require_eq!(position_token.amount, 1, StakingError::NotPositionOwner);
+
+    token::burn(
+        CpiContext::new(
+            token_program.to_account_info(),
+            Burn {
+                mint: position_mint.to_account_info(),
+                from: position_token.to_account_info(),
+                authority: user.to_account_info(),
+            },
+        ),
+        1,
+    )
+}
+
+// Books the tier's partner reward (if the staker passed a partner reward with a rate for it)
+// against that partner's reserve
+fn _attach_partner_reward(
+    partner_reward: &mut Option<Account<PartnerReward>>,
+    position: &mut Account<StakePosition>,
+    duration_days: u64,
+    snrg_decimals: u8,
+) -> Result<()> {
+    let partner = match partner_reward.as_mut() {
+        Some(partner) => partner,
+        None => return Ok(()),
+    };
+    let units_per_snrg = match partner.rates.get(&duration_days) {
+        Some(rate) => *rate,
+        None => return Ok(()),
+    };
+
+    let reward = _partner_reward_of(position.amount, units_per_snrg, snrg_decimals)?;
+    let required = partner.promised_rewards.checked_add(reward).ok_or(StakingError::MathOverflow)?;
+    require!(partner.reward_reserve >= required, StakingError::PartnerReserveShortfall);
+    partner.promised_rewards = required;
+
+    position.partner_mint = partner.reward_mint;
+    position.partner_reward = reward;
+    Ok(())
+}
+
+// Pays (`paid`) or forfeits a position's partner reward; the partner accounts are required
+// whenever the position carries one
+fn _settle_partner_reward(accounts: &mut Withdraw, stake_index: u64, paid: bool) -> Result<()> {
+    let position = &accounts.stake_position;
+    if position.partner_reward == 0 {
+        return Ok(());
+    }
+
+    let partner = accounts.partner_reward.as_mut().ok_or(StakingError::InvalidPartnerReward)?;
+    require_keys_eq!(partner.reward_mint, position.partner_mint, StakingError::InvalidPartnerReward);
+    partner.promised_rewards = partner.promised_rewards.checked_sub(position.partner_reward).ok_or(StakingError::MathOverflow)?;
+
+    if paid {
+        let (mint, vault, user_token) = match (&accounts.partner_mint, &accounts.partner_vault, &accounts.user_partner_token) {
+            (Some(m), Some(v), Some(t)) => (m, v, t),
+            _ => return err!(StakingError::InvalidPartnerReward),
+        };
+        require_keys_eq!(vault.key(), partner.vault, StakingError::InvalidPartnerReward);
+        partner.reward_reserve = partner.reward_reserve.checked_sub(position.partner_reward).ok_or(StakingError::MathOverflow)?;
+
+        let staking_key = accounts.staking.key();
+        let seeds = &[b"vault_authority", staking_key.as_ref(), &[accounts.staking.vault_authority_bump]];
+        token::transfer_checked(
+            CpiContext::new_with_signer(
+                accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: vault.to_account_info(),
+                    to: user_token.to_account_info(),
+                    authority: accounts.vault_authority.to_account_info(),
+                    mint: mint.to_account_info(),
+                },
+                &[&seeds[..]],
+            ),
+            position.partner_reward,
+            mint.decimals,
+        )?;
+
+        emit!(PartnerRewardPaid {
+            user: accounts.user.key(),
+            stake_index,
+            mint: partner.reward_mint,
+            amount: position.partner_reward,
+        });
+    }
+
+    Ok(())
+}
+
+// Reward earned pro rata to elapsed time, scaled by the tier's payout; 0 for tiers without a policy
+fn _early_reward(staking: &Staking, stake: &StakePosition, now: i64) -> Result<u64> {
+    let payout_bps = match staking.early_reward_payout.get(&stake.duration_days) {
+        Some(bps) => *bps,
+        None => return Ok(0),
+    };
+
+    let term = stake.end_time.saturating_sub(stake.start_time).max(1) as u128;
+    let elapsed = now.clamp(stake.start_time, stake.end_time).saturating_sub(stake.start_time) as u128;
+    let earned = (stake.reward as u128)
+        .checked_mul(elapsed)
+        .ok_or(StakingError::MathOverflow)?
+        / term;
+    let earned = u64::try_from(earned).map_err(|_| StakingError::MathOverflow)?;
+    _bps_of(earned, payout_bps)
+}
+
+// Reward-per-token at `now` and the emission it took; emission is capped by the unpromised
+// reserve so the flexible pool never makes the contract insolvent
+fn _flex_accrual(staking: &Staking, now: i64) -> Result<(u128, u64)> {
+    if now <= staking.flex_last_update || staking.flex_total_staked == 0 || staking.flex_reward_rate == 0 {
+        return Ok((staking.flex_reward_per_token, 0));
+    }
+
+    let elapsed = (now - staking.flex_last_update) as u64;
+    let available = staking.reward_reserve.saturating_sub(staking.promised_rewards);
+    let emitted = staking.flex_reward_rate.saturating_mul(elapsed).min(available);
+    let increase = (emitted as u128)
+        .checked_mul(FLEX_PRECISION)
+        .ok_or(StakingError::MathOverflow)?
+        / staking.flex_total_staked as u128;
+    let reward_per_token = staking.flex_reward_per_token.checked_add(increase).ok_or(StakingError::MathOverflow)?;
+    Ok((reward_per_token, emitted))
+}
+
+fn _update_flex_pool(staking: &mut Staking, now: i64) -> Result<()> {
+    let (reward_per_token, emitted) = _flex_accrual(staking, now)?;
+    staking.flex_reward_per_token = reward_per_token;
+    staking.promised_rewards = staking.promised_rewards.checked_add(emitted).ok_or(StakingError::MathOverflow)?;
+    staking.flex_promised = staking.flex_promised.checked_add(emitted).ok_or(StakingError::MathOverflow)?;
+    staking.flex_last_update = staking.flex_last_update.max(now);
+    Ok(())
+}
+
+fn _flex_pending(position: &FlexPosition, reward_per_token: u128) -> Result<u64> {
+    let accrued = (position.amount as u128)
+        .checked_mul(reward_per_token - position.reward_per_token_paid)
+        .ok_or(StakingError::MathOverflow)?
+        / FLEX_PRECISION;
+    let accrued = u64::try_from(accrued).map_err(|_| StakingError::MathOverflow)?;
+    Ok(position.pending_rewards.checked_add(accrued).ok_or(StakingError::MathOverflow)?)
+}
+
+fn _flex_position_open(position: &FlexPosition) -> bool {
+    position.amount > 0 || position.pending_rewards > 0
+}
+
+// Keeps the open flex position count; once every position is settled, the rounding dust left
+// in `flex_promised` is released so promised rewards can reach zero again
+fn _track_flex_position(staking: &mut Staking, was_open: bool, position: &FlexPosition) -> Result<()> {
+    match (was_open, _flex_position_open(position)) {
+        (false, true) => {
+            staking.flex_open_positions = staking.flex_open_positions.checked_add(1).ok_or(StakingError::MathOverflow)?;
+        }
+        (true, false) => {
+            staking.flex_open_positions = staking.flex_open_positions.checked_sub(1).ok_or(StakingError::MathOverflow)?;
+        }
+        _ => {}
+    }
+
+    if staking.flex_open_positions == 0 && staking.flex_promised > 0 {
+        let dust = staking.flex_promised;
+        staking.promised_rewards = staking.promised_rewards.checked_sub(dust).ok_or(StakingError::MathOverflow)?;
+        staking.flex_promised = 0;
+        emit!(FlexDustReleased { amount: dust });
+    }
+    Ok(())
+}
+
+fn _settle_flex_position(staking: &Staking, position: &mut FlexPosition) -> Result<()> {
+    position.pending_rewards = _flex_pending(position, staking.flex_reward_per_token)?;
+    position.reward_per_token_paid = staking.flex_reward_per_token;
+    Ok(())
+}
+
+// Prices a new term stake against the reserve, books capacity and fills the position; bumps are
+// left to the caller. Returns (stake_index, reward, end_time).
+fn _open_stake(
+    staking: &mut Account<Staking>,
+    user_stakes: &mut Account<UserStakes>,
+    position: &mut Account<StakePosition>,
+    owner: Pubkey,
+    amount: u64,
+    duration_days: u64,
+    locked: bool,
+) -> Result<(u64, u64, i64)> {
+    let now = Clock::get()?.unix_timestamp;
+    let reward = _promise_term_reward(staking, amount, duration_days, now)?;
+    _take_capacity(staking, user_stakes, duration_days, amount)?;
+
+    let end_time = now.checked_add((duration_days as i64) * 86_400).ok_or(StakingError::MathOverflow)?;
+
+    let stake_index = user_stakes.stake_count;
+    user_stakes.user = owner;
+    user_stakes.stake_count = stake_index.checked_add(1).ok_or(StakingError::MathOverflow)?;
+    user_stakes.active_stakes = user_stakes.active_stakes.checked_add(1).ok_or(StakingError::MathOverflow)?;
+    staking.active_stakes = staking.active_stakes.checked_add(1).ok_or(StakingError::MathOverflow)?;
+
+    position.user = owner;
+    position.index = stake_index;
+    position.amount = amount;
+    position.reward = reward;
+    position.start_time = now;
+    position.duration_days = duration_days;
+    position.end_time = end_time;
+    position.position_mint = Pubkey::default();
+    position.locked = locked;
+    position.partner_mint = Pubkey::default();
+    position.partner_reward = 0;
+
+    Ok((stake_index, reward, end_time))
+}
+
+// Books a new term's reward against the unpromised reserve. Flex emission accrued up to `now`
+// is booked first, so a term stake can never take reserve the flex pool has already earned.
+fn _promise_term_reward(staking: &mut Staking, amount: u64, duration_days: u64, now: i64) -> Result<u64> {
+    _update_flex_pool(staking, now)?;
+    let reward = _term_reward(staking, amount, duration_days)?;
+    let required_reserve = staking.promised_rewards.checked_add(reward).ok_or(StakingError::MathOverflow)?;
+    if staking.reward_reserve < required_reserve {
+        emit!(ReserveShortfall { required: required_reserve, available: staking.reward_reserve });
+        return err!(StakingError::InsufficientReserves);
+    }
+    staking.promised_rewards = required_reserve;
+    Ok(reward)
+}
+
+fn _term_reward(staking: &Staking, amount: u64, duration_days: u64) -> Result<u64> {
+    let reward_bps = staking.reward_rates.get(&duration_days).ok_or(StakingError::InvalidDuration)?;
+    _bps_of(amount, *reward_bps)
+}
+
+// Pauses (once) when reserves fall below promised rewards or the vault below principal plus
+// promised rewards; returns whether the contract is insolvent. Runs after every vault outflow.
+fn _solvency_guard(staking: &mut Staking, vault_balance: u64) -> Result<bool> {
+    let principal = staking.total_term_staked.checked_add(staking.flex_total_staked).ok_or(StakingError::MathOverflow)?;
+    let required = principal.checked_add(staking.promised_rewards).ok_or(StakingError::MathOverflow)?;
+    let insolvent = staking.reward_reserve < staking.promised_rewards || vault_balance < required;
+
+    if insolvent && !staking.paused {
+        staking.paused = true;
+        emit!(AutoPaused {
+            reward_reserve: staking.reward_reserve,
+            promised_rewards: staking.promised_rewards,
+            vault_balance,
+            required,
+        });
+    }
+    Ok(insolvent)
+}
+
+// The position account itself is closed by the `close = user` constraint
+fn _close_stake_slot(
+    staking: &mut Account<Staking>,
+    user_stakes: &mut Account<UserStakes>,
+    duration_days: u64,
+    amount: u64,
+) -> Result<()> {
+    staking.active_stakes = staking.active_stakes.checked_sub(1).ok_or(StakingError::MathOverflow)?;
+    user_stakes.active_stakes = user_stakes.active_stakes.checked_sub(1).ok_or(StakingError::MathOverflow)?;
+    _release_capacity(staking, user_stakes, duration_days, amount)
+}
+
+// Capacity limits (0 / absent = unlimited)
+fn _check_tvl_cap(staking: &Staking, amount: u64) -> Result<()> {
+    let tvl = staking.total_term_staked.checked_add(staking.flex_total_staked).ok_or(StakingError::MathOverflow)?;
+    let tvl = tvl.checked_add(amount).ok_or(StakingError::MathOverflow)?;
+    require!(staking.tvl_cap == 0 || tvl <= staking.tvl_cap, StakingError::TvlCapExceeded);
+    Ok(())
+}
+
+fn _take_capacity(staking: &mut Staking, user_stakes: &mut UserStakes, duration_days: u64, amount: u64) -> Result<()> {
+    _check_tvl_cap(staking, amount)?;
+
+    // Retired tiers keep their entry while principal is open; the account holds 2 * MAX_REWARD_RATES
+    require!(
+        staking.tier_staked.contains_key(&duration_days) || staking.tier_staked.len() < 2 * MAX_REWARD_RATES,
+        StakingError::TooManyTiers
+    );
+    let tier_staked = staking.tier_staked.get(&duration_days).copied().unwrap_or(0);
+    let tier_staked = tier_staked.checked_add(amount).ok_or(StakingError::MathOverflow)?;
+    if let Some(cap) = staking.tier_caps.get(&duration_days) {
+        require!(tier_staked <= *cap, StakingError::TierCapExceeded);
+    }
+
+    let user_principal = user_stakes.active_principal.checked_add(amount).ok_or(StakingError::MathOverflow)?;
+    require!(
+        staking.max_user_principal == 0 || user_principal <= staking.max_user_principal,
+        StakingError::UserCapExceeded
+    );
+
+    staking.tier_staked.insert(duration_days, tier_staked);
+    staking.total_term_staked = staking.total_term_staked.checked_add(amount).ok_or(StakingError::MathOverflow)?;
+    user_stakes.active_principal = user_principal;
+    Ok(())
+}
+
+fn _release_capacity(staking: &mut Staking, user_stakes: &mut UserStakes, duration_days: u64, amount: u64) -> Result<()> {
+    let tier_staked = staking.tier_staked.get(&duration_days).copied().unwrap_or(0);
+    let tier_staked = tier_staked.checked_sub(amount).ok_or(StakingError::MathOverflow)?;
+    if tier_staked == 0 {
+        staking.tier_staked.remove(&duration_days);
+    } else {
+        staking.tier_staked.insert(duration_days, tier_staked);
+    }
+    staking.total_term_staked = staking.total_term_staked.checked_sub(amount).ok_or(StakingError::MathOverflow)?;
+    user_stakes.active_principal = user_stakes.active_principal.checked_sub(amount).ok_or(StakingError::MathOverflow)?;
+    Ok(())
+}
+
+// Rate changes apply immediately without a delay, otherwise they wait in the pending queue
+fn _queue_rate_change(staking: &mut Account<Staking>, duration_days: u64, reward_bps: Option<u64>) -> Result<()> {
+    require!(
+        !staking.pending_rate_changes.iter().any(|c| c.duration_days == duration_days),
+        StakingError::RateChangePending
+    );
+
+    if staking.rate_change_delay == 0 {
+        return _apply_rate_change(staking, duration_days, reward_bps);
+    }
+
+    require!(staking.pending_rate_changes.len() < MAX_PENDING_RATE_CHANGES, StakingError::TooManyPendingChanges);
+    let eta = Clock::get()?.unix_timestamp.checked_add(staking.rate_change_delay).ok_or(StakingError::MathOverflow)?;
+    staking.pending_rate_changes.push(PendingRateChange { duration_days, reward_bps, eta });
+
+    emit!(RateChangeQueued { duration_days, reward_bps, eta });
+    Ok(())
+}
+
+fn _apply_rate_change(staking: &mut Account<Staking>, duration_days: u64, reward_bps: Option<u64>) -> Result<()> {
+    match reward_bps {
+        Some(reward_bps) => {
+            require!(
+                staking.reward_rates.contains_key(&duration_days) || staking.reward_rates.len() < MAX_REWARD_RATES,
+                StakingError::TooManyRewardRates
+            );
+            staking.reward_rates.insert(duration_days, reward_bps);
+            emit!(RewardRateSet { duration_days, reward_bps });
+        }
+        None => {
+            staking.reward_rates.remove(&duration_days);
+            emit!(RewardRateRemoved { duration_days });
+        }
+    }
+    Ok(())
+}
+
+// Partner base units owed on `amount` SNRG base units at `units_per_snrg` per whole SNRG, so the
+// rate reads the same whatever the two mints' decimals are
+fn _partner_reward_of(amount: u64, units_per_snrg: u64, snrg_decimals: u8) -> Result<u64> {
+    let one_snrg = 10u128.checked_pow(u32::from(snrg_decimals)).ok_or(StakingError::MathOverflow)?;
+    let value = (amount as u128)
+        .checked_mul(units_per_snrg as u128)
+        .ok_or(StakingError::MathOverflow)?
+        / one_snrg;
+    u64::try_from(value).map_err(|_| error!(StakingError::MathOverflow))
+}
+
+fn _bps_of(amount: u64, bps: u64) -> Result<u64> {
+    let value = amount
+        .checked_mul(bps)
+        .ok_or(StakingError::MathOverflow)?
+        .checked_div(BPS_DENOMINATOR)
+        .ok_or(StakingError::MathOverflow)?;
+    Ok(value)
+}
+
+// CPI helpers
+fn _transfer_to_vault(accounts: &impl GetAccounts, amount: u64) -> Result<()> {
+    token::transfer_checked(
+        CpiContext::new(
+            accounts.token_program().to_account_info(),
+            TransferChecked {
+                from: accounts.treasury_token().to_account_info(),
+                to: accounts.vault().to_account_info(),
+                authority: accounts.treasury_authority().to_account_info(),
+                mint: accounts.mint().to_account_info(),
+            },
+        ),
+        amount,
+        accounts.mint().decimals,
+    )
+}
+
+fn _transfer_to_vault_user_to_program(accounts: &StakeTokens, amount: u64) -> Result<()> {
+    token::transfer_checked(
+        CpiContext::new(
+            accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: accounts.user_token.to_account_info(),
+                to: accounts.vault.to_account_info(),
+                authority: accounts.user.to_account_info(),
+                mint: accounts.mint.to_account_info(),
+            },
+        ),
+        amount,
+        accounts.mint.decimals,
+    )
+}
+
+fn _transfer_from_vault(accounts: &impl GetAccounts, amount: u64) -> Result<()> {
+    let staking_key = accounts.staking().key();
+    let seeds = &[b"vault_authority", staking_key.as_ref(), &[accounts.staking().vault_authority_bump]];
+    token::transfer_checked(
+        CpiContext::new_with_signer(
+            accounts.token_program().to_account_info(),
+            TransferChecked {
+                from: accounts.vault().to_account_info(),
+                to: accounts.user_token().to_account_info(),
+                authority: accounts.vault_authority().to_account_info(),
+                mint: accounts.mint().to_account_info(),
+            },
+            &[&seeds[..]],
+        ),
+        amount,
+        accounts.mint().decimals,
+    )
+}
+
+fn _transfer_vault_to_treasury(accounts: &impl GetAccounts, amount: u64) -> Result<()> {
+    let staking_key = accounts.staking().key();
+    let seeds = &[b"vault_authority", staking_key.as_ref(), &[accounts.staking().vault_authority_bump]];
+    token::transfer_checked(
+        CpiContext::new_with_signer(
+            accounts.token_program().to_account_info(),
+            TransferChecked {
+                from: accounts.vault().to_account_info(),
+                to: accounts.treasury_token().to_account_info(),
+                authority: accounts.vault_authority().to_account_info(),
+                mint: accounts.mint().to_account_info(),
+            },
+            &[&seeds[..]],
+        ),
+        amount,
+        accounts.mint().decimals,
+    )
+}
+
+fn _transfer_from_vault_to_user(accounts: &impl GetAccounts, amount: u64) -> Result<()> {
+    _transfer_from_vault(accounts, amount)
+}
+
+fn _transfer_partner_to_treasury(accounts: &PartnerTreasury, amount: u64) -> Result<()> {
+    let staking_key = accounts.staking.key();
+    let seeds = &[b"vault_authority", staking_key.as_ref(), &[accounts.staking.vault_authority_bump]];
+    token::transfer_checked(
+        CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: accounts.partner_vault.to_account_info(),
+                to: accounts.treasury_token.to_account_info(),
+                authority: accounts.vault_authority.to_account_info(),
+                mint: accounts.reward_mint.to_account_info(),
+            },
+            &[&seeds[..]],
+        ),
+        amount,
+        accounts.reward_mint.decimals,
+    )
+}
+
+// Accounts
+#[account]
+pub struct Staking {
+    pub treasury: Pubkey,
+    pub snrg_mint: Pubkey,
+    pub vault_authority: Pubkey,
+    pub vault: Pubkey, // the one SNRG token account whose balance backs principal and rewards
+    pub is_funded: bool,
+    pub paused: bool,
+    pub reward_reserve: u64,
+    pub promised_rewards: u64,
+    pub reward_rates: std::collections::BTreeMap<u64, u64>, // days → bps
+    pub rate_change_delay: i64,
+    pub pending_rate_change_delay: i64, // queued decrease, applied at `rate_change_delay_eta`
+    pub rate_change_delay_eta: i64,     // 0 = nothing queued
+    pub pending_rate_changes: Vec<PendingRateChange>,
+    pub early_withdrawal_fee_bps: u64,
+    pub emergency_fee_bps: u64,
+    pub fee_burn_bps: u64,     // penalty split, sums to BPS_DENOMINATOR
+    pub fee_treasury_bps: u64,
+    pub fee_reserve_bps: u64,
+    pub flex_reward_rate: u64,       // flexible pool emission per second
+    pub flex_total_staked: u64,
+    pub flex_reward_per_token: u128, // scaled by FLEX_PRECISION
+    pub flex_last_update: i64,
+    pub flex_promised: u64,       // emitted to the flexible pool and not yet claimed
+    pub flex_open_positions: u64, // flex positions holding principal or unclaimed rewards
+    pub active_stakes: u64, // open term positions across all users
+    pub early_reward_payout: std::collections::BTreeMap<u64, u64>, // days → bps of pro-rata reward
+    pub tvl_cap: u64,            // 0 = unlimited
+    pub max_user_principal: u64, // 0 = unlimited
+    pub total_term_staked: u64,
+    pub tier_caps: std::collections::BTreeMap<u64, u64>,   // days → max principal
+    pub tier_staked: std::collections::BTreeMap<u64, u64>, // days → open principal
+    pub emergency_mode: bool,
+    pub partner_mints: Vec<Pubkey>,
+    pub partner_tiers: std::collections::BTreeMap<u64, Pubkey>, // days → the tier's only partner mint
+    pub position_base_uri: String,
+    pub vault_authority_bump: u8,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PendingRateChange {
+    pub duration_days: u64,
+    pub reward_bps: Option<u64>, // None = remove the duration
+    pub eta: i64,
+}
+
+#[account]
+pub struct UserStakes {
+    pub user: Pubkey,
+    pub stake_count: u64,   // next stake index
+    pub active_stakes: u64, // positions not yet withdrawn
+    pub active_principal: u64,
+    pub bump: u8,
+}
+
+// One PDA per stake: [b"stake", staking, user, index]
+#[account]
+pub struct StakePosition {
+    pub user: Pubkey,
+    pub index: u64,
+    pub amount: u64,
+    pub reward: u64,
+    pub start_time: i64,
+    pub duration_days: u64,
+    pub end_time: i64,
+    pub position_mint: Pubkey, // default = not tokenized; otherwise the NFT holder owns the stake
+    pub locked: bool,          // granted via stake_for: no withdrawal before end_time
+    pub partner_mint: Pubkey,  // default = no partner reward
+    pub partner_reward: u64,
+    pub bump: u8,
+}
+
+// Partner co-incentive for one reward mint: [b"partner_reward", staking, reward_mint]
+#[account]
+pub struct PartnerReward {
+    pub staking: Pubkey,
+    pub reward_mint: Pubkey,
+    pub vault: Pubkey, // token account owned by vault_authority
+    pub reward_reserve: u64,
+    pub promised_rewards: u64,
+    pub rates: std::collections::BTreeMap<u64, u64>, // days → partner base units per whole SNRG
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ReserveInfo {
+    pub mint: Pubkey,
+    pub reserve: u64,
+    pub promised: u64,
+    pub available: u64,
+}
+
+// Flexible pool position: [b"flex", staking, user]
+#[account]
+pub struct FlexPosition {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub reward_per_token_paid: u128,
+    pub pending_rewards: u64,
+    pub bump: u8,
+}
+
+// Contexts (condensed — full versions available)
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(mut)] pub payer: Signer<'info>,
+    pub snrg_mint: Account<'info, Mint>,
+    /// CHECK: treasury
+    pub treasury: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 32 + 32 + 32 + 32 + 1 + 1 + 8 + 8 + (4 + MAX_REWARD_RATES * 16) + 8 + 8 + 8
+            + (4 + MAX_PENDING_RATE_CHANGES * (8 + 9 + 8)) + 8 + 8 + 8 + 8 + 8
+            + 8 + 8 + 16 + 8 + 8 + 8 + 8 + (4 + MAX_REWARD_RATES * 16)
+            + 8 + 8 + 8 + (4 + MAX_REWARD_RATES * 16) + (4 + 2 * MAX_REWARD_RATES * 16) + 1
+            + (4 + MAX_PARTNER_MINTS * 32) + (4 + MAX_REWARD_RATES * 40) + (4 + MAX_POSITION_BASE_URI_LEN) + 1 + 1,
+        seeds = [b"staking", treasury.key().as_ref()],
+        bump,
+    )]
+    pub staking: Account<'info, Staking>,
+    #[account(seeds = [b"vault_authority", staking.key().as_ref()], bump)]
+    /// CHECK: PDA
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(token::mint = snrg_mint, token::authority = vault_authority)]
+    pub vault: Account<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct StakeTokens<'info> {
+    #[account(mut, seeds = [b"staking", staking.treasury.as_ref()], bump = staking.bump)]
+    pub staking: Account<'info, Staking>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 32 + 8 + 8 + 8 + 1,
+        seeds = [b"user_stakes", staking.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub user_stakes: Account<'info, UserStakes>,
+    #[account(
+        init,
+        payer = user,
+        space = 8 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 32 + 1 + 32 + 8 + 1,
+        seeds = [b"stake", staking.key().as_ref(), user.key().as_ref(), &user_stakes.stake_count.to_le_bytes()],
+        bump,
+    )]
+    pub stake_position: Account<'info, StakePosition>,
+    #[account(mut)] pub user: Signer<'info>,
+    #[account(mut, token::mint = mint, token::authority = user)]
+    pub user_token: Account<'info, TokenAccount>,
+    #[account(mut, address = staking.vault)]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(seeds = [b"vault_authority", staking.key().as_ref()], bump)]
+    /// CHECK: PDA
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(address = staking.snrg_mint)]
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"partner_reward", staking.key().as_ref(), partner_reward.reward_mint.as_ref()],
+        bump = partner_reward.bump,
+    )]
+    pub partner_reward: Option<Account<'info, PartnerReward>>,
+    pub bonus_funder: Option<Signer<'info>>,
+    // Owner or delegate is checked by the token program
+    #[account(mut, token::mint = mint)]
+    pub bonus_source: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
 
-// Contexts (condensed — full versions available)
 #[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(mut)] pub payer: Signer<'info>,
-    pub snrg_mint: Account<'info, Mint>,
-    /// CHECK: treasury
-    pub treasury: UncheckedAccount<'info>,
+pub struct StakeFor<'info> {
+    #[account(mut, seeds = [b"staking", staking.treasury.as_ref()], bump = staking.bump)]
+    pub staking: Account<'info, Staking>,
+    #[account(
+        init_if_needed,
+        payer = funder,
+        space = 8 + 32 + 8 + 8 + 8 + 1,
+        seeds = [b"user_stakes", staking.key().as_ref(), beneficiary.key().as_ref()],
+        bump,
+    )]
+    pub user_stakes: Account<'info, UserStakes>,
     #[account(
         init,
-        payer = payer,
-        space = 8 + 32 + 32 + 32 + 1 + 1 + 8 + 8 + 300 + 1,
-        seeds = [b"staking", treasury.key().as_ref()],
+        payer = funder,
+        space = 8 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 32 + 1 + 32 + 8 + 1,
+        seeds = [b"stake", staking.key().as_ref(), beneficiary.key().as_ref(), &user_stakes.stake_count.to_le_bytes()],
+        bump,
+    )]
+    pub stake_position: Account<'info, StakePosition>,
+    #[account(mut)] pub funder: Signer<'info>,
+    #[account(mut, token::mint = mint, token::authority = funder)]
+    pub funder_token: Account<'info, TokenAccount>,
+    /// CHECK: any wallet; receives the stake
+    pub beneficiary: UncheckedAccount<'info>,
+    #[account(mut, address = staking.vault)]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(seeds = [b"vault_authority", staking.key().as_ref()], bump)]
+    /// CHECK: PDA
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(address = staking.snrg_mint)]
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"partner_reward", staking.key().as_ref(), partner_reward.reward_mint.as_ref()],
+        bump = partner_reward.bump,
+    )]
+    pub partner_reward: Option<Account<'info, PartnerReward>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(stake_index: u64)]
+pub struct Withdraw<'info> {
+    #[account(mut, seeds = [b"staking", staking.treasury.as_ref()], bump = staking.bump)]
+    pub staking: Account<'info, Staking>,
+    #[account(
+        mut,
+        seeds = [b"user_stakes", staking.key().as_ref(), stake_position.user.as_ref()],
+        bump = user_stakes.bump,
+    )]
+    pub user_stakes: Account<'info, UserStakes>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"stake", staking.key().as_ref(), stake_position.user.as_ref(), &stake_index.to_le_bytes()],
+        bump = stake_position.bump,
+    )]
+    pub stake_position: Account<'info, StakePosition>,
+    /// Original staker, or the position NFT holder
+    #[account(mut)] pub user: Signer<'info>,
+    #[account(mut)]
+    pub position_mint: Option<Account<'info, Mint>>,
+    #[account(mut, token::mint = position_mint, token::authority = user)]
+    pub position_token: Option<Account<'info, TokenAccount>>,
+    #[account(mut, token::mint = mint, token::authority = user)]
+    pub user_token: Account<'info, TokenAccount>,
+    #[account(mut, address = staking.vault)]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(seeds = [b"vault_authority", staking.key().as_ref()], bump)]
+    /// CHECK: PDA
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut, token::mint = mint, token::authority = staking.treasury)]
+    pub treasury_token: Account<'info, TokenAccount>,
+    #[account(mut, address = staking.snrg_mint)]
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"partner_reward", staking.key().as_ref(), partner_reward.reward_mint.as_ref()],
+        bump = partner_reward.bump,
+    )]
+    pub partner_reward: Option<Account<'info, PartnerReward>>,
+    pub partner_mint: Option<Account<'info, Mint>>,
+    #[account(mut, token::mint = partner_mint, token::authority = vault_authority)]
+    pub partner_vault: Option<Account<'info, TokenAccount>>,
+    #[account(mut, token::mint = partner_mint, token::authority = user)]
+    pub user_partner_token: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawAllMatured<'info> {
+    #[account(mut, seeds = [b"staking", staking.treasury.as_ref()], bump = staking.bump)]
+    pub staking: Account<'info, Staking>,
+    #[account(
+        mut,
+        has_one = user,
+        seeds = [b"user_stakes", staking.key().as_ref(), user.key().as_ref()],
+        bump = user_stakes.bump,
+    )]
+    pub user_stakes: Account<'info, UserStakes>,
+    #[account(mut)] pub user: Signer<'info>,
+    #[account(mut, token::mint = mint, token::authority = user)]
+    pub user_token: Account<'info, TokenAccount>,
+    #[account(mut, address = staking.vault)]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(seeds = [b"vault_authority", staking.key().as_ref()], bump)]
+    /// CHECK: PDA
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(address = staking.snrg_mint)]
+    pub mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(stake_index: u64)]
+pub struct TokenizePosition<'info> {
+    #[account(seeds = [b"staking", staking.treasury.as_ref()], bump = staking.bump)]
+    pub staking: Account<'info, Staking>,
+    #[account(
+        mut,
+        has_one = user,
+        seeds = [b"stake", staking.key().as_ref(), user.key().as_ref(), &stake_index.to_le_bytes()],
+        bump = stake_position.bump,
+    )]
+    pub stake_position: Account<'info, StakePosition>,
+    #[account(mut)] pub user: Signer<'info>,
+    #[account(
+        init,
+        payer = user,
+        mint::decimals = 0,
+        mint::authority = vault_authority,
+        mint::freeze_authority = vault_authority,
+        seeds = [b"position_mint", stake_position.key().as_ref()],
+        bump,
+    )]
+    pub position_mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = user,
+        associated_token::mint = position_mint,
+        associated_token::authority = user,
+    )]
+    pub position_token: Account<'info, TokenAccount>,
+    /// CHECK: created by the token metadata program
+    #[account(mut)]
+    pub metadata: UncheckedAccount<'info>,
+    #[account(seeds = [b"vault_authority", staking.key().as_ref()], bump)]
+    /// CHECK: PDA
+    pub vault_authority: UncheckedAccount<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct ViewPosition<'info> {
+    pub stake_position: Account<'info, StakePosition>,
+}
+
+#[derive(Accounts)]
+#[instruction(stake_index: u64)]
+pub struct Restake<'info> {
+    #[account(mut, seeds = [b"staking", staking.treasury.as_ref()], bump = staking.bump)]
+    pub staking: Account<'info, Staking>,
+    #[account(
+        mut,
+        seeds = [b"user_stakes", staking.key().as_ref(), user.key().as_ref()],
+        bump = user_stakes.bump,
+    )]
+    pub user_stakes: Account<'info, UserStakes>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"stake", staking.key().as_ref(), user.key().as_ref(), &stake_index.to_le_bytes()],
+        bump = old_position.bump,
+    )]
+    pub old_position: Account<'info, StakePosition>,
+    #[account(
+        init,
+        payer = user,
+        space = 8 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 32 + 1 + 32 + 8 + 1,
+        seeds = [b"stake", staking.key().as_ref(), user.key().as_ref(), &user_stakes.stake_count.to_le_bytes()],
+        bump,
+    )]
+    pub new_position: Account<'info, StakePosition>,
+    #[account(mut)] pub user: Signer<'info>,
+    #[account(mut, token::mint = mint, token::authority = user)]
+    pub user_token: Account<'info, TokenAccount>,
+    #[account(mut, address = staking.vault)]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(seeds = [b"vault_authority", staking.key().as_ref()], bump)]
+    /// CHECK: PDA
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(address = staking.snrg_mint)]
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"partner_reward", staking.key().as_ref(), partner_reward.reward_mint.as_ref()],
+        bump = partner_reward.bump,
+    )]
+    pub partner_reward: Option<Account<'info, PartnerReward>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FlexDeposit<'info> {
+    #[account(mut, seeds = [b"staking", staking.treasury.as_ref()], bump = staking.bump)]
+    pub staking: Account<'info, Staking>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 32 + 8 + 16 + 8 + 1,
+        seeds = [b"flex", staking.key().as_ref(), user.key().as_ref()],
         bump,
     )]
+    pub flex_position: Account<'info, FlexPosition>,
+    #[account(mut)] pub user: Signer<'info>,
+    #[account(mut, token::mint = mint, token::authority = user)]
+    pub user_token: Account<'info, TokenAccount>,
+    #[account(mut, address = staking.vault)]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(seeds = [b"vault_authority", staking.key().as_ref()], bump)]
+    /// CHECK: PDA
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(address = staking.snrg_mint)]
+    pub mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FlexWithdraw<'info> {
+    #[account(mut, seeds = [b"staking", staking.treasury.as_ref()], bump = staking.bump)]
+    pub staking: Account<'info, Staking>,
+    #[account(
+        mut,
+        has_one = user,
+        seeds = [b"flex", staking.key().as_ref(), user.key().as_ref()],
+        bump = flex_position.bump,
+    )]
+    pub flex_position: Account<'info, FlexPosition>,
+    pub user: Signer<'info>,
+    #[account(mut, token::mint = mint, token::authority = user)]
+    pub user_token: Account<'info, TokenAccount>,
+    #[account(mut, address = staking.vault)]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(seeds = [b"vault_authority", staking.key().as_ref()], bump)]
+    /// CHECK: PDA
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(address = staking.snrg_mint)]
+    pub mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ViewFlex<'info> {
+    pub staking: Account<'info, Staking>,
+    #[account(seeds = [b"flex", staking.key().as_ref(), flex_position.user.as_ref()], bump = flex_position.bump)]
+    pub flex_position: Account<'info, FlexPosition>,
+}
+
+#[derive(Accounts)]
+pub struct ViewUserCapacity<'info> {
+    pub staking: Account<'info, Staking>,
+    #[account(seeds = [b"user_stakes", staking.key().as_ref(), user_stakes.user.as_ref()], bump = user_stakes.bump)]
+    pub user_stakes: Account<'info, UserStakes>,
+}
+
+#[derive(Accounts)]
+pub struct ViewUser<'info> {
+    pub user_stakes: Account<'info, UserStakes>,
+}
+
+#[derive(Accounts)]
+pub struct Admin<'info> {
+    #[account(mut, has_one = treasury, seeds = [b"staking", treasury.key().as_ref()], bump = staking.bump)]
+    pub staking: Account<'info, Staking>,
+    pub treasury: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TreasuryWithdraw<'info> {
+    #[account(mut, has_one = treasury, seeds = [b"staking", treasury.key().as_ref()], bump = staking.bump)]
+    pub staking: Account<'info, Staking>,
+    #[account(mut)]
+    pub treasury: Signer<'info>,
+    #[account(mut, address = staking.vault)]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(seeds = [b"vault_authority", staking.key().as_ref()], bump)]
+    /// CHECK: PDA
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut, token::mint = mint, token::authority = treasury)]
+    pub treasury_token: Account<'info, TokenAccount>,
+    #[account(address = staking.snrg_mint)]
+    pub mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CloseStaking<'info> {
+    #[account(
+        mut,
+        close = treasury,
+        has_one = treasury,
+        seeds = [b"staking", treasury.key().as_ref()],
+        bump = staking.bump,
+    )]
+    pub staking: Account<'info, Staking>,
+    #[account(mut)]
+    pub treasury: Signer<'info>,
+    #[account(mut, address = staking.vault)]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(seeds = [b"vault_authority", staking.key().as_ref()], bump)]
+    /// CHECK: PDA
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut, token::mint = mint, token::authority = treasury)]
+    pub treasury_token: Account<'info, TokenAccount>,
+    #[account(address = staking.snrg_mint)]
+    pub mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CheckSolvency<'info> {
+    #[account(mut, seeds = [b"staking", staking.treasury.as_ref()], bump = staking.bump)]
+    pub staking: Account<'info, Staking>,
+    #[account(address = staking.vault)]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(seeds = [b"vault_authority", staking.key().as_ref()], bump)]
+    /// CHECK: PDA
+    pub vault_authority: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddPartnerReward<'info> {
+    #[account(mut, has_one = treasury, seeds = [b"staking", treasury.key().as_ref()], bump = staking.bump)]
     pub staking: Account<'info, Staking>,
+    #[account(mut)]
+    pub treasury: Signer<'info>,
+    pub reward_mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = treasury,
+        space = 8 + 32 + 32 + 32 + 8 + 8 + (4 + MAX_REWARD_RATES * 16) + 1,
+        seeds = [b"partner_reward", staking.key().as_ref(), reward_mint.key().as_ref()],
+        bump,
+    )]
+    pub partner_reward: Account<'info, PartnerReward>,
+    #[account(
+        init,
+        payer = treasury,
+        token::mint = reward_mint,
+        token::authority = vault_authority,
+        seeds = [b"partner_vault", partner_reward.key().as_ref()],
+        bump,
+    )]
+    pub partner_vault: Account<'info, TokenAccount>,
     #[account(seeds = [b"vault_authority", staking.key().as_ref()], bump)]
     /// CHECK: PDA
     pub vault_authority: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct FundPartnerReward<'info> {
+    #[account(mut, has_one = reward_mint)]
+    pub partner_reward: Account<'info, PartnerReward>,
+    pub reward_mint: Account<'info, Mint>,
+    #[account(mut, address = partner_reward.vault)]
+    pub partner_vault: Account<'info, TokenAccount>,
+    pub funder: Signer<'info>,
+    #[account(mut, token::mint = reward_mint, token::authority = funder)]
+    pub funder_token: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct PartnerTreasury<'info> {
+    #[account(mut, has_one = treasury, seeds = [b"staking", treasury.key().as_ref()], bump = staking.bump)]
+    pub staking: Account<'info, Staking>,
+    #[account(mut)]
+    pub treasury: Signer<'info>,
+    #[account(
+        mut,
+        has_one = staking,
+        has_one = reward_mint,
+        seeds = [b"partner_reward", staking.key().as_ref(), reward_mint.key().as_ref()],
+        bump = partner_reward.bump,
+    )]
+    pub partner_reward: Account<'info, PartnerReward>,
+    pub reward_mint: Account<'info, Mint>,
+    #[account(mut, address = partner_reward.vault)]
+    pub partner_vault: Account<'info, TokenAccount>,
+    #[account(seeds = [b"vault_authority", staking.key().as_ref()], bump)]
+    /// CHECK: PDA
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut, token::mint = reward_mint, token::authority = treasury)]
+    pub treasury_token: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct AdminPartner<'info> {
+    #[account(mut, has_one = treasury, seeds = [b"staking", treasury.key().as_ref()], bump = staking.bump)]
+    pub staking: Account<'info, Staking>,
+    pub treasury: Signer<'info>,
+    #[account(
+        mut,
+        has_one = staking,
+        seeds = [b"partner_reward", staking.key().as_ref(), partner_reward.reward_mint.as_ref()],
+        bump = partner_reward.bump,
+    )]
+    pub partner_reward: Account<'info, PartnerReward>,
+}
+
+#[derive(Accounts)]
+pub struct ViewStaking<'info> {
+    pub staking: Account<'info, Staking>,
+}
+
 // All other contexts follow similar pattern — fully implemented in production version
 
 // Events & Errors — 100% match to Solidity
@@ -371,12 +2341,88 @@ pub struct ReserveShortfall { pub required: u64, pub available: u64 }
 #[event]
 pub struct Staked { pub user: Pubkey, pub stake_index: u64, pub amount: u64, pub reward: u64, pub end_time: i64 }
 #[event]
+pub struct StakedFor { pub funder: Pubkey, pub beneficiary: Pubkey, pub stake_index: u64, pub locked: bool }
+#[event]
+pub struct StakeBonusAdded { pub user: Pubkey, pub stake_index: u64, pub bonus: u64, pub funder: Pubkey }
+#[event]
 pub struct Withdrawn { pub user: Pubkey, pub stake_index: u64, pub amount: u64, pub reward: u64 }
 #[event]
-pub struct WithdrawnEarly { pub user: Pubkey, pub stake_index: u64, pub amount: u64, pub fee: u64 }
+pub struct PositionBaseUriSet { pub base_uri: String }
+#[event]
+pub struct PositionTokenized { pub user: Pubkey, pub stake_index: u64, pub mint: Pubkey, pub amount: u64, pub reward: u64, pub end_time: i64 }
+#[event]
+pub struct Restaked {
+    pub user: Pubkey,
+    pub old_stake_index: u64,
+    pub new_stake_index: u64,
+    pub amount: u64,
+    pub reward: u64,
+    pub end_time: i64,
+    pub compounded: bool,
+}
+#[event]
+pub struct WithdrawnEarly { pub user: Pubkey, pub stake_index: u64, pub amount: u64, pub reward: u64, pub fee: u64 }
 #[event]
 pub struct EmergencyWithdrawal { pub user: Pubkey, pub stake_index: u64, pub amount: u64, pub fee: u64 }
 #[event]
+pub struct RateChangeDelaySet { pub delay: i64 }
+#[event]
+pub struct RateChangeDelayQueued { pub delay: i64, pub eta: i64 }
+#[event]
+pub struct RateChangeQueued { pub duration_days: u64, pub reward_bps: Option<u64>, pub eta: i64 }
+#[event]
+pub struct RateChangeCanceled { pub duration_days: u64 }
+#[event]
+pub struct RewardRateSet { pub duration_days: u64, pub reward_bps: u64 }
+#[event]
+pub struct RewardRateRemoved { pub duration_days: u64 }
+#[event]
+pub struct WithdrawalFeesSet { pub early_bps: u64, pub emergency_bps: u64 }
+#[event]
+pub struct FeeSplitSet { pub burn_bps: u64, pub treasury_bps: u64, pub reserve_bps: u64 }
+#[event]
+pub struct PenaltyDistributed { pub burned: u64, pub to_treasury: u64, pub to_reserve: u64 }
+#[event]
+pub struct FlexRewardRateSet { pub rate_per_second: u64 }
+#[event]
+pub struct FlexDeposited { pub user: Pubkey, pub amount: u64, pub total_staked: u64 }
+#[event]
+pub struct FlexWithdrawn { pub user: Pubkey, pub amount: u64, pub total_staked: u64 }
+#[event]
+pub struct FlexRewardsClaimed { pub user: Pubkey, pub amount: u64 }
+#[event]
+pub struct FlexDustReleased { pub amount: u64 }
+#[event]
+pub struct ExcessReserveWithdrawn { pub amount: u64, pub remaining_reserve: u64 }
+#[event]
+pub struct StakingClosed { pub swept: u64 }
+#[event]
+pub struct EarlyRewardPayoutSet { pub duration_days: u64, pub payout_bps: Option<u64> }
+#[event]
+pub struct TvlCapSet { pub cap: u64 }
+#[event]
+pub struct TierCapSet { pub duration_days: u64, pub cap: Option<u64> }
+#[event]
+pub struct MaxUserPrincipalSet { pub max: u64 }
+#[event]
+pub struct EmergencyModeSet { pub enabled: bool }
+#[event]
+pub struct AutoPaused { pub reward_reserve: u64, pub promised_rewards: u64, pub vault_balance: u64, pub required: u64 }
+#[event]
+pub struct BatchWithdrawn { pub user: Pubkey, pub count: u64, pub amount: u64, pub reward: u64 }
+#[event]
+pub struct PartnerRewardAdded { pub mint: Pubkey }
+#[event]
+pub struct PartnerRewardFunded { pub mint: Pubkey, pub amount: u64 }
+#[event]
+pub struct PartnerExcessWithdrawn { pub mint: Pubkey, pub amount: u64, pub remaining_reserve: u64 }
+#[event]
+pub struct PartnerRewardRemoved { pub mint: Pubkey, pub swept: u64 }
+#[event]
+pub struct PartnerRateSet { pub mint: Pubkey, pub duration_days: u64, pub units_per_snrg: Option<u64> }
+#[event]
+pub struct PartnerRewardPaid { pub user: Pubkey, pub stake_index: u64, pub mint: Pubkey, pub amount: u64 }
+#[event]
 pub struct ContractPaused;
 #[event]
 pub struct ContractUnpaused;
@@ -386,4 +2432,231 @@ pub enum StakingError {
     ZeroAmount, ZeroAddress, InvalidDuration, MathOverflow, AlreadyWithdrawn,
     StakeNotMatured, StakeMatured, InvalidIndex, InsufficientReserves, FeeExceedsAmount,
     AlreadyFunded, NotFunded, Paused, NotPaused, AlreadyPaused,
-}
\ No newline at end of file
+    RateTooHigh, TooManyRewardRates, TooManyPendingChanges, RateChangePending,
+    NoPendingRateChange, RateChangeNotReady, InvalidDelay,
+    FeeTooHigh, InvalidFeeSplit, InsufficientFlexBalance, NothingToClaim,
+    StakesOutstanding, InvalidPayout, TvlCapExceeded, TierCapExceeded, UserCapExceeded,
+    PositionTokenized, NotPositionOwner, StakeLocked,
+    EmergencyMode, NotEmergencyMode, TooManyPartnerMints, InvalidPartnerReward,
+    PartnerReserveShortfall, PartnerRewardPending, InvalidBatchSize, InvalidStakePosition,
+    NothingToWithdraw, MissingBonusAccounts, TooManyTiers, PositionUriUnset, PositionUriTooLong,
+    TierHasPartner, PartnerRewardsOutstanding,
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn staking() -> Staking {
+        Staking {
+            treasury: Pubkey::default(),
+            snrg_mint: Pubkey::default(),
+            vault_authority: Pubkey::default(),
+            vault: Pubkey::default(),
+            is_funded: true,
+            paused: false,
+            reward_reserve: 0,
+            promised_rewards: 0,
+            reward_rates: BTreeMap::from([(30, 125), (90, 375)]),
+            rate_change_delay: 0,
+            pending_rate_change_delay: 0,
+            rate_change_delay_eta: 0,
+            pending_rate_changes: Vec::new(),
+            early_withdrawal_fee_bps: EARLY_WITHDRAWAL_FEE_BPS,
+            emergency_fee_bps: EMERGENCY_FEE_BPS,
+            fee_burn_bps: 0,
+            fee_treasury_bps: BPS_DENOMINATOR,
+            fee_reserve_bps: 0,
+            flex_reward_rate: 0,
+            flex_total_staked: 0,
+            flex_reward_per_token: 0,
+            flex_last_update: 0,
+            flex_promised: 0,
+            flex_open_positions: 0,
+            active_stakes: 0,
+            early_reward_payout: BTreeMap::new(),
+            tvl_cap: 0,
+            max_user_principal: 0,
+            total_term_staked: 0,
+            tier_caps: BTreeMap::new(),
+            tier_staked: BTreeMap::new(),
+            emergency_mode: false,
+            partner_mints: Vec::new(),
+            partner_tiers: BTreeMap::new(),
+            position_base_uri: String::new(),
+            vault_authority_bump: 255,
+            bump: 255,
+        }
+    }
+
+    fn flex_pool(rate: u64, staked: u64, reserve: u64, promised: u64) -> Staking {
+        Staking {
+            flex_reward_rate: rate,
+            flex_total_staked: staked,
+            reward_reserve: reserve,
+            promised_rewards: promised,
+            flex_last_update: 1_000,
+            ..staking()
+        }
+    }
+
+    #[test]
+    fn flex_accrual_emits_rate_over_elapsed_time() {
+        let pool = flex_pool(10, 1_000, 1_000_000, 0);
+        let (reward_per_token, emitted) = _flex_accrual(&pool, 1_100).unwrap();
+        assert_eq!(emitted, 1_000);
+        assert_eq!(reward_per_token, FLEX_PRECISION); // 1 token of reward per staked token
+    }
+
+    #[test]
+    fn flex_accrual_is_capped_by_unpromised_reserve() {
+        let pool = flex_pool(10, 1_000, 500, 200);
+        let (_, emitted) = _flex_accrual(&pool, 1_100).unwrap();
+        assert_eq!(emitted, 300);
+    }
+
+    #[test]
+    fn flex_accrual_is_idle_without_stakers_rate_or_time() {
+        for (pool, now) in [
+            (flex_pool(10, 0, 1_000_000, 0), 1_100),
+            (flex_pool(0, 1_000, 1_000_000, 0), 1_100),
+            (flex_pool(10, 1_000, 1_000_000, 0), 1_000),
+            (flex_pool(10, 1_000, 1_000_000, 0), 900),
+        ] {
+            assert_eq!(_flex_accrual(&pool, now).unwrap(), (0, 0));
+        }
+    }
+
+    #[test]
+    fn flex_update_books_emission_as_promised() {
+        let mut pool = flex_pool(10, 1_000, 1_000_000, 0);
+        _update_flex_pool(&mut pool, 1_100).unwrap();
+        assert_eq!(pool.promised_rewards, 1_000);
+        assert_eq!(pool.flex_last_update, 1_100);
+        // A clock behind the last update never rewinds it
+        _update_flex_pool(&mut pool, 1_050).unwrap();
+        assert_eq!(pool.flex_last_update, 1_100);
+    }
+
+    #[test]
+    fn term_stake_cannot_take_accrued_flex_emission() {
+        // 100s of flex at 10/s accrued 1_000 before the term stake arrives; 1_100 unpromised
+        let mut pool = flex_pool(10, 1_000, 1_100, 0);
+        // 90 days at 3.75% on 8_000 wants 300, but only 100 is left once flex is booked
+        assert!(_promise_term_reward(&mut pool, 8_000, 90, 1_100).is_err());
+
+        let reward = _promise_term_reward(&mut pool, 2_000, 90, 1_100).unwrap();
+        assert_eq!(reward, 75);
+        assert_eq!(pool.flex_promised, 1_000);
+        assert_eq!(pool.promised_rewards, 1_075);
+        // Flex stakers keep their full earnings for the elapsed time
+        assert_eq!(pool.flex_reward_per_token, FLEX_PRECISION);
+    }
+
+    #[test]
+    fn flex_dust_is_released_once_every_position_settles() {
+        let mut pool = flex_pool(10, 1_000, 1_000_000, 0);
+        pool.flex_open_positions = 2;
+        _update_flex_pool(&mut pool, 1_100).unwrap();
+
+        // Both positions claimed and withdrew; rounding left 3 of the 1_000 emitted unclaimed
+        pool.promised_rewards -= 997;
+        pool.flex_promised -= 997;
+        let closed = FlexPosition { user: Pubkey::default(), amount: 0, reward_per_token_paid: 0, pending_rewards: 0, bump: 0 };
+        _track_flex_position(&mut pool, true, &closed).unwrap();
+        assert_eq!(pool.promised_rewards, 3);
+        _track_flex_position(&mut pool, true, &closed).unwrap();
+        assert_eq!(pool.flex_open_positions, 0);
+        assert_eq!(pool.flex_promised, 0);
+        assert_eq!(pool.promised_rewards, 0);
+    }
+
+    fn position(reward: u64, start_time: i64, end_time: i64) -> StakePosition {
+        StakePosition {
+            user: Pubkey::default(),
+            index: 0,
+            amount: 100_000,
+            reward,
+            start_time,
+            duration_days: 30,
+            end_time,
+            position_mint: Pubkey::default(),
+            locked: false,
+            partner_mint: Pubkey::default(),
+            partner_reward: 0,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn early_reward_is_zero_without_payout_policy() {
+        assert_eq!(_early_reward(&staking(), &position(1_000, 0, 100), 50).unwrap(), 0);
+    }
+
+    #[test]
+    fn early_reward_pays_policy_share_of_elapsed_reward() {
+        let mut pool = staking();
+        pool.early_reward_payout.insert(30, 5_000);
+        let stake = position(1_000, 0, 100);
+        assert_eq!(_early_reward(&pool, &stake, 50).unwrap(), 250);
+        assert_eq!(_early_reward(&pool, &stake, -10).unwrap(), 0);
+        assert_eq!(_early_reward(&pool, &stake, 500).unwrap(), 500); // elapsed is capped at the term
+    }
+
+    fn user_stakes() -> UserStakes {
+        UserStakes { user: Pubkey::default(), stake_count: 0, active_stakes: 0, active_principal: 0, bump: 255 }
+    }
+
+    #[test]
+    fn capacity_is_taken_and_released_per_tier() {
+        let mut pool = staking();
+        let mut user = user_stakes();
+        _take_capacity(&mut pool, &mut user, 30, 400).unwrap();
+        _take_capacity(&mut pool, &mut user, 30, 100).unwrap();
+        assert_eq!(pool.tier_staked.get(&30), Some(&500));
+        assert_eq!(pool.total_term_staked, 500);
+        assert_eq!(user.active_principal, 500);
+
+        _release_capacity(&mut pool, &mut user, 30, 500).unwrap();
+        assert!(pool.tier_staked.is_empty());
+        assert_eq!(pool.total_term_staked, 0);
+        assert_eq!(user.active_principal, 0);
+        assert!(_release_capacity(&mut pool, &mut user, 30, 1).is_err());
+    }
+
+    #[test]
+    fn capacity_enforces_tier_user_and_tvl_caps() {
+        let mut pool = staking();
+        pool.tier_caps.insert(30, 1_000);
+        pool.max_user_principal = 800;
+        pool.tvl_cap = 1_500;
+        let mut user = user_stakes();
+        assert!(_take_capacity(&mut pool, &mut user, 30, 1_001).is_err());
+        assert!(_take_capacity(&mut pool, &mut user, 90, 801).is_err());
+        _take_capacity(&mut pool, &mut user, 90, 800).unwrap();
+
+        let mut other = user_stakes();
+        _take_capacity(&mut pool, &mut other, 30, 700).unwrap();
+        assert!(_take_capacity(&mut pool, &mut other, 30, 1).is_err()); // TVL cap reached
+    }
+
+    #[test]
+    fn tier_staked_map_is_bounded() {
+        let mut pool = staking();
+        let mut user = user_stakes();
+        for days in 1..=(2 * MAX_REWARD_RATES as u64) {
+            _take_capacity(&mut pool, &mut user, days, 1).unwrap();
+        }
+        assert!(_take_capacity(&mut pool, &mut user, 1_000, 1).is_err());
+        _take_capacity(&mut pool, &mut user, 1, 1).unwrap(); // existing tiers still accept stakes
+    }
+
+    #[test]
+    fn partner_reward_is_per_whole_snrg() {
+        // 2.5 SNRG (9 decimals) at 4 partner units per SNRG, whatever the partner mint's decimals
+        assert_eq!(_partner_reward_of(2_500_000_000, 4_000_000, 9).unwrap(), 10_000_000);
+        assert_eq!(_partner_reward_of(2_500_000_000, 4_000_000_000_000_000_000, 9).unwrap(), 10_000_000_000_000_000_000);
+        assert_eq!(_partner_reward_of(1, 1, 9).unwrap(), 0); // rounds down
+        assert!(_partner_reward_of(u64::MAX, u64::MAX, 0).is_err());
+    }
+}